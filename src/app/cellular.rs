@@ -1,4 +1,8 @@
-use crate::{matrix, prelude::*};
+use crate::{
+    field::{Bounds, HexField},
+    palette::{self, Palette16},
+    prelude::*,
+};
 use defmt::info;
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 
@@ -22,44 +26,62 @@ impl Seed {
     }
 }
 pub struct Fire {
-    data: [f32; 21 * 19],
+    field: HexField<f32>,
     count: u8,
     rng: SmallRng,
-    seeds: [Seed; 16],
+    seeds: [Seed; SEEDS],
     bias: f32,
+    pub palette: Palette16,
 }
+
+fn origin() -> Vec2 {
+    Vec2::new(Matrix::ORIGIN.0 as i32, Matrix::ORIGIN.1 as i32)
+}
+
+const SEEDS: usize = 16;
+
+// SEEDS cells spread evenly along the bottom of the matrix: the lowest led of every column,
+// left to right. Taken from the untransformed layout, so the fire keeps burning from the
+// same logical cells and turns with the display transform like everything else.
+fn bottom_rim() -> [Vec2; SEEDS] {
+    let mut rim = [Vec2::new(0, 0); Matrix::WIDTH];
+    let mut n = 0;
+    for x in 0..Matrix::WIDTH {
+        if let Some(y) = (0..Matrix::HEIGHT)
+            .rev()
+            .find(|y| Matrix::led_addr(x, *y).is_ok())
+        {
+            rim[n] = Vec2::new(x as i32, y as i32);
+            n += 1;
+        }
+    }
+    if n == 0 {
+        return [origin(); SEEDS];
+    }
+    core::array::from_fn(|i| rim[i * (n - 1) / (SEEDS - 1)])
+}
+
 pub fn new() -> Fire {
-    let mut data = [0.0; MATRIX_HEIGHT * MATRIX_WIDTH];
-    data[10 * 19 + 10] = 1.0;
-    let seeds = [
-        Seed::new(2, 15),
-        Seed::new(3, 16),
-        Seed::new(4, 16),
-        Seed::new(5, 16),
-        Seed::new(6, 16),
-        Seed::new(6, 17),
-        Seed::new(7, 18),
-        Seed::new(7, 19),
-        Seed::new(8, 20),
-        Seed::new(9, 20),
-        Seed::new(10, 20),
-        Seed::new(11, 20),
-        Seed::new(12, 20),
-        Seed::new(13, 20),
-        Seed::new(14, 20),
-        Seed::new(15, 20),
-    ];
+    with_palette(palette::FIRE)
+}
+
+// e.g. with_palette(palette::LAVA)
+pub fn with_palette(palette: Palette16) -> Fire {
+    let mut field = HexField::new(Bounds::Zero);
+    field.set(origin(), 1.0);
+    let seeds = bottom_rim().map(|v| Seed::new(v.x, v.y));
     Fire {
-        data,
+        field,
         count: 0,
         rng: SmallRng::seed_from_u64(0),
         seeds,
         bias: 0.5,
+        palette,
     }
 }
 
 impl App for Fire {
-    fn tick(&mut self, led_data: &mut [RGB8; NUM_LEDS], env: &Env) {
+    fn tick(&mut self, led_data: &mut [RGB8; Matrix::NUM_LEDS], env: &Env) {
         // vary activity between ~ 40 - 100 db
         // FIXME: the second mems behaves weirly in the complete build. Maybe noise?
         let act = ((env.spl_db - 55.0) / 50.0).clamp(0.03, 1.0);
@@ -86,9 +108,9 @@ impl App for Fire {
         //     }
         // }
         for s in self.seeds {
-            self.set(s.pos, s.temperature);
+            self.field.set(s.pos, s.temperature);
         }
-        let mut burning = [false; 16];
+        let mut burning = [false; SEEDS];
         for (b, s) in burning.iter_mut().zip(self.seeds.iter()) {
             *b = s.burning;
         }
@@ -116,72 +138,22 @@ impl App for Fire {
         // let bias_range = 0.2;
         self.bias = (self.bias + self.rng.gen_range(-0.1..0.1) * 0.5).clamp(0.15, 0.85);
         // info!("bias: {}", self.bias);
-        let mut new_data = self.data.clone();
         // let bias = self.rng.gen_range(0.0..bias_range);
         let feedback = 0.87;
         let up = 0.1;
-        for y in 0..21 {
-            for x in 0..19 {
-                let v = Vec2::new(x, y);
-                let adj = matrix::adjacent(v);
-                self.set(
-                    v,
-                    self.get(v) * feedback
-                        + self.get(adj[2]) * self.bias * up
-                        + self.get(adj[3]) * (1.0 - self.bias) * up,
-                )
-            }
-        }
-        let r = 255.0;
-        let g = 80.0;
-        let b = 8.0;
-        for (data, led) in self.data.iter().zip(crate::matrix::MATRIX_MAP.iter()) {
-            let i = *led as usize;
-            let data = data.clamp(0.0, 1.0);
-            if i < NUM_LEDS {
-                led_data[i] = RGB8::new(
-                    ((r * data) as u8).clamp(0, 255),
-                    ((g * data) as u8).clamp(0, 255),
-                    ((b * data) as u8).clamp(0, 255),
-                );
-                // led_data[i] = led_data[i] = (&HV8 {
-                //     h: 20,
-                //     // v: self.count,
-                //     v: (data.clamp(0.0, 1.0) * 255.0) as u8,
-                // })
-                //     .into();
-            }
-        }
+        let bias = self.bias;
+        // heat rises from the two cells below
+        self.field.step(|f, v| {
+            let adj = f.neighbors(v);
+            f.get(v) * feedback + adj[4] * bias * up + adj[5] * (1.0 - bias) * up
+        });
+        self.field.to_leds_palette(led_data, &self.palette);
         self.count = self.count.wrapping_add(1);
     }
 }
 
-impl Fire {
-    fn set(&mut self, v: Vec2, f: f32) {
-        if v.x < 0 || v.x as usize >= MATRIX_WIDTH || v.y < 0 || v.y as usize >= MATRIX_HEIGHT {
-            return;
-        }
-        let addr = MATRIX_WIDTH * v.y as usize + v.x as usize;
-        self.data[addr] = f;
-    }
-    fn get(&self, v: Vec2) -> f32 {
-        if v.x < 0 || v.x as usize >= MATRIX_WIDTH || v.y < 0 || v.y as usize >= MATRIX_HEIGHT {
-            return 0.0;
-        }
-        let addr = MATRIX_WIDTH * v.y as usize + v.x as usize;
-        self.data[addr]
-    }
-    // fn get_mut(&mut self, v: Vec2) -> &mut f32 {
-    //             if v.x < 0 || v.x as usize >= MATRIX_WIDTH || v.y < 0 || v.y as usize >= MATRIX_HEIGHT {
-    //         return 0.0;
-    //     }
-    //     let addr = MATRIX_WIDTH * v.y as usize + v.x as usize;
-    //     self.data[addr]
-    // }
-}
-
 pub struct FireWorks {
-    data: [f32; 21 * 19],
+    field: HexField<f32>,
     count: u8,
     rng: SmallRng,
     // seeds: [Seed; 16],
@@ -189,66 +161,28 @@ pub struct FireWorks {
 
 impl FireWorks {
     pub fn new() -> FireWorks {
-        let mut data = [0.0; MATRIX_HEIGHT * MATRIX_WIDTH];
-
-        data[10 * 19 + 10] = 1.0;
+        let mut field = HexField::new(Bounds::Zero);
+        field.set(origin(), 1.0);
         FireWorks {
-            data,
+            field,
             count: 0,
             rng: SmallRng::seed_from_u64(0),
             // seeds,
         }
     }
-    fn set(&mut self, v: Vec2, f: f32) {
-        if v.x < 0 || v.x as usize >= MATRIX_WIDTH || v.y < 0 || v.y as usize >= MATRIX_HEIGHT {
-            return;
-        }
-        let addr = MATRIX_WIDTH * v.y as usize + v.x as usize;
-        self.data[addr] = f;
-    }
-    fn get(&self, v: Vec2) -> f32 {
-        if v.x < 0 || v.x as usize >= MATRIX_WIDTH || v.y < 0 || v.y as usize >= MATRIX_HEIGHT {
-            return 0.0;
-        }
-        let addr = MATRIX_WIDTH * v.y as usize + v.x as usize;
-        self.data[addr]
-    }
 }
 impl App for FireWorks {
-    fn tick(&mut self, led_data: &mut [RGB8; NUM_LEDS], _env: &Env) {
+    fn tick(&mut self, led_data: &mut [RGB8; Matrix::NUM_LEDS], _env: &Env) {
         let feedback = 0.31;
         let up = 0.1;
-        for y in 0..21 {
-            for x in 0..19 {
-                let v = Vec2::new(x, y);
-                let adj = matrix::adjacent(v);
-                self.set(
-                    v,
-                    self.get(v) * feedback
-                        + self.get(adj[0]) * up
-                        + self.get(adj[1]) * up
-                        + self.get(adj[2]) * up
-                        + self.get(adj[3]) * up
-                        + self.get(adj[4]) * up
-                        + self.get(adj[5]) * up,
-                )
-            }
-        }
+        // in place, the heat spreads down and right within a single tick
+        self.field
+            .update(|f, v| f.get(v) * feedback + f.neighbors(v).iter().sum::<f32>() * up);
         let r = 255.0;
         let g = 80.0;
-        let b = 0.0;
-        for (data, led) in self.data.iter().zip(crate::matrix::MATRIX_MAP.iter()) {
-            let i = *led as usize;
+        self.field.to_leds(led_data, |data| {
             let data = data.clamp(0.0, 1.0);
-            if i < NUM_LEDS {
-                led_data[i] = RGB8::new(((r * data) as u8).clamp(0, 255), (g * data) as u8, 0);
-                // led_data[i] = led_data[i] = (&HV8 {
-                //     h: 20,
-                //     // v: self.count,
-                //     v: (data.clamp(0.0, 1.0) * 255.0) as u8,
-                // })
-                //     .into();
-            }
-        }
+            RGB8::new(((r * data) as u8).clamp(0, 255), (g * data) as u8, 0)
+        });
     }
 }
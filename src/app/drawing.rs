@@ -1,9 +1,9 @@
 use canvas::Canvas;
 use smart_leds::brightness;
 
-use crate::hex::prelude::*;
-use crate::math::Vec2;
-use crate::prelude::*;
+use crate::{color::RGB16, prelude::*};
+// sin / cos, inherent when the tests link std
+#[cfg(not(test))]
 use micromath::F32Ext;
 
 pub struct Drawing {
@@ -19,32 +19,34 @@ pub fn new() -> Drawing {
 }
 
 impl app::App for Drawing {
-    fn tick(&mut self, canvas: &mut [RGB8; NUM_LEDS], _env: &Env) {
-        if self.i >= (360 / 6) {
-            self.i = 0;
-        }
-        let i = self.i;
+    fn tick(&mut self, canvas: &mut [RGB8; Matrix::NUM_LEDS], _env: &Env) {
         // canvas.clear();
         canvas.iter_mut().for_each(|v| {
             *v = brightness(core::iter::once(*v), 210).next().unwrap();
         });
+        self.draw(canvas);
+    }
+    // the trail fades out smoothly instead of in the last few 8 bit steps
+    fn tick16(&mut self, canvas: &mut [RGB16; Matrix::NUM_LEDS], _env: &Env) {
+        canvas.iter_mut().for_each(|v| *v = color::scale16(*v, 210));
+        self.draw(canvas);
+    }
+}
+
+impl Drawing {
+    fn draw<C: Canvas>(&mut self, canvas: &mut C) {
+        if self.i >= (360 / 6) {
+            self.i = 0;
+        }
+        let i = self.i;
         let f = ((i * 6) as f32).to_radians();
         let s = f.sin();
         let c = f.cos();
-        // let (sin, cos) = f.sin();
-        // f.sin()
-        // let v0 = Vec2::new((s * -5f32) as i32, (c * -5f32) as i32);
-        let v0 = Cube::zero();
-        let v = Vec2::new((s * 90f32) as i32, (c * 90f32) as i32);
-
-        canvas.line(
-            v0,
-            v.into(),
+        canvas.line_aa(
+            (0.0, 0.0),
+            (s * 12f32, c * 12f32),
             brightness(&mut self.rainbow, 32).next().unwrap(),
         );
         self.i += 1;
-        // canvas.data()[1] = RGB8::default();
-        // periphery.delay.delay_ms(8u8);
-        // let v =
     }
 }
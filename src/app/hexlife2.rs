@@ -1,6 +1,11 @@
-use crate::{bitzet::Bitzet, math::Vec2, prelude::*};
+use crate::{
+    color::RGB16,
+    life::{Automaton, Boundary, Rule},
+    oklab::Lab,
+    pattern,
+    prelude::*,
+};
 
-type BitzetN = Bitzet<128>;
 // const LERP_TIME: i32 = 60 * 1;
 // const PAUSE_TIME: i32 = 60 * 2;
 
@@ -8,196 +13,113 @@ const LERP_TIME: i32 = 60 * 5;
 const PAUSE_TIME: i32 = 60 * 10;
 
 pub struct Hexlife2 {
-    black: BitzetN,
+    life: Automaton,
 
     i: usize,
-    keep_on: [u32; NUM_LEDS / 32 + 1],
+    keep_on: [u32; Matrix::NUM_LEDS / 32 + 1],
 
     // rainbow: Rainbow,
     rainbow: u8,
 
-    last: [HV8; NUM_LEDS],
+    last: [HSV8; Matrix::NUM_LEDS],
 
-    next: [HV8; NUM_LEDS],
+    next: [HSV8; Matrix::NUM_LEDS],
+    // last and next in OKLab, the transition is mixed there
+    from: [Lab; Matrix::NUM_LEDS],
+    to: [Lab; Matrix::NUM_LEDS],
     f: i32,
 }
 
 pub fn new() -> Hexlife2 {
-    let mut black = BitzetN::new();
-    for line in input().iter() {
-        let mut c = line.chars();
-        let mut x = 0i32;
-        let mut y = 0i32;
-
-        // data[i % NUM_LEDS] = RGB8::new(0, 255, 0);
-        // let mut prev = None;
-        // fn reset_prev(prev: Option<(i16, RGB8)>, data: &mut [RGB8]) {
-        //     if let Some((led, color)) = prev {
-        //         data[led as usize] = color;
-        //     }
-        // }
-        loop {
-            match c.next() {
-                Some('e') => x += 1,
-                Some('w') => x -= 1,
-                Some('s') => match c.next() {
-                    Some('e') => {
-                        x += y.abs() % 2;
-                        y += 1
-                    }
-                    Some('w') => {
-                        y += 1;
-                        x -= y.abs() % 2;
-                    }
-                    _ => break,
-                },
-                Some('n') => match c.next() {
-                    Some('e') => {
-                        x += y.abs() % 2;
-                        y -= 1
-                    }
-                    Some('w') => {
-                        y -= 1;
-                        x -= y.abs() % 2;
-                    }
-                    _ => break,
-                },
-                None => break,
-
-                _ => break,
-            }
-        }
+    with_rule(Rule::HEXLIFE)
+}
 
-        if black.contains(&Vec2 { x, y }) {
-            black.remove(&Vec2 { x, y });
-        } else {
-            black.insert(Vec2 { x, y });
-        }
-    }
+// e.g. with_rule(Rule::parse("B2/S34/C4")?)
+pub fn with_rule(rule: Rule) -> Hexlife2 {
+    let mut life = Automaton::new(rule, Boundary::Dead);
+    pattern::HEXLIFE
+        .load(|v| life.toggle(v))
+        .expect("bad hexlife pattern");
 
     Hexlife2 {
-        black,
+        life,
         i: 0,
-        keep_on: [0u32; NUM_LEDS / 32 + 1],
+        keep_on: [0u32; Matrix::NUM_LEDS / 32 + 1],
         rainbow: 0,
-        next: [HV8::zero(); NUM_LEDS],
-        last: [HV8::zero(); NUM_LEDS],
+        next: [HSV8::new(0, 255, 0); Matrix::NUM_LEDS],
+        last: [HSV8::new(0, 255, 0); Matrix::NUM_LEDS],
+        from: [Lab::default(); Matrix::NUM_LEDS],
+        to: [Lab::default(); Matrix::NUM_LEDS],
         f: LERP_TIME,
     }
 }
 
-impl app::App for Hexlife2 {
-    fn tick(&mut self, led_data: &mut [RGB8; NUM_LEDS], _env: &Env) {
-        // let mut rainbow = Rainbow::step(7);
-
-        if self.f >= LERP_TIME + PAUSE_TIME {
-            let mut black_new = Bitzet::new();
-
-            for v in self.black.iter() {
-                let n = adjacent(v)
-                    .iter()
-                    .filter(|v| self.black.contains(v))
-                    .count();
-                if (1..=2).contains(&n) {
-                    black_new.insert(v);
-                }
-            }
-
-            let white = self
-                .black
-                .iter()
-                .flat_map(|v| adjacent(v).into_iter())
-                .collect::<BitzetN>();
-
-            let white = white.difference(&self.black);
-            for v in white.iter() {
-                let n = adjacent(v)
-                    .iter()
-                    .filter(|v| self.black.contains(v))
-                    .count();
-                if n == 2 && v.x.abs() < 15 && v.y.abs() < 15 {
-                    black_new.insert(v);
-                }
-            }
+impl Hexlife2 {
+    // Every LERP_TIME + PAUSE_TIME frames the next generation, followed by a LERP_TIME
+    // frames fade to it. Returns the fade position for frames that need to be drawn.
+    fn advance(&mut self) -> Option<u8> {
+        let t = if self.f >= LERP_TIME + PAUSE_TIME {
+            self.step();
+            self.f = 0;
+            Some(0)
+        } else if self.f <= LERP_TIME {
+            Some((self.f * 255 / LERP_TIME) as u8)
+        } else {
+            None
+        };
+        self.i = self.i.overflowing_add(1).0;
+        self.f = self.f.overflowing_add(1).0;
+        t
+    }
 
-            core::mem::swap(&mut self.black, &mut black_new);
-            let black_old = black_new; // rebind to new name
+    fn step(&mut self) {
+        self.life.step();
 
-            self.last = self.next;
+        self.last = self.next;
 
-            self.next.iter_mut().for_each(|hv| hv.v = 0);
-            self.keep_on.fill(0);
-            for v in self.black.iter() {
-                if let Ok(addr) = led_addr((v.x + 10) as usize, (v.y + 10) as usize) {
+        self.next.iter_mut().for_each(|hv| hv.v = 0);
+        self.keep_on.fill(0);
+        let states = self.life.rule.states as u32;
+        for (v, state) in self.life.iter() {
+            if let Ok(addr) = led_addr_oddr(v) {
+                if state == 1 {
                     self.next[addr].h = self.rainbow;
                     self.rainbow += 7;
-                    if !black_old.contains(&v) {
-                        self.last[addr].h = self.next[addr].h;
-                    }
-                    self.next[addr].v = 255;
+                } else {
+                    // aging cells of Generations rules keep their color and fade out
+                    self.next[addr].h = self.last[addr].h;
                 }
+                self.next[addr].v = (255 * (states - state as u32) / (states - 1)) as u8;
             }
-            self.f = 0;
+        }
+        self.from = self.to;
+        for (to, c) in self.to.iter_mut().zip(self.next.iter()) {
+            *to = RGB8::from(c).into();
+        }
+    }
 
-            for (out, hv) in led_data.iter_mut().zip(self.last.iter()) {
-                *out = hv.into();
-            }
-            // *led_data = self.last;
-        } else if self.f <= LERP_TIME {
-            for (out, (last, next)) in led_data
-                .iter_mut()
-                .zip(self.last.iter().zip(self.next.iter()))
-            {
-                let h = last.h as i32 + (next.h as i32 - last.h as i32) * self.f / LERP_TIME;
-                let v = last.v as i32 + (next.v as i32 - last.v as i32) * self.f / LERP_TIME;
-
-                *out = (&HV8 {
-                    h: h as u8,
-                    v: v as u8,
-                })
-                    .into();
-            }
+    // mix last and next generation in OKLab, t = 0 is last
+    fn draw<C: From<Lab>>(&self, led_data: &mut [C], t: u8) {
+        for (out, (from, to)) in led_data
+            .iter_mut()
+            .zip(self.from.iter().zip(self.to.iter()))
+        {
+            *out = from.lerp(to, t).into();
         }
-        self.i = self.i.overflowing_add(1).0;
-        self.f = self.f.overflowing_add(1).0;
     }
 }
 
-fn input() -> &'static [&'static str] {
-    &[
-        "eeeee",
-        "wwwwwwswsw",
-        "neneeseswswswee",
-        "w",
-        "wwwwwwswswee",
-        "wnwnwwswswsese",
-        "wwwwwwnenwwsw",
-        "wnwnwwswsw",
-        "eeeeene",
-        "eeeeese",
-        "wnwnw",
-        "wnwnww",
-        "neneesesw",
-        "wwwwwwnenww",
-        "ne",
-        "wnwnwwswswse",
-        "wnwnww",
-        "neneeseswswswe",
-        "wwwwww",
-        "eeeeesesw",
-        "nene",
-        "wwwwwwswswe",
-        "neneeseswsw",
-        "wwwwwwne",
-        "eeeeenenw",
-        "wnwnwwsw",
-        "neneese",
-        "wnwnwwswswsesee",
-        "wnwnwwswswseseene",
-        "wnw",
-        "wwwwwwnenw",
-        "wwwwwwsw",
-        "nenee",
-        "neneeseswswsw",
-    ]
+impl app::App for Hexlife2 {
+    fn tick(&mut self, led_data: &mut [RGB8; Matrix::NUM_LEDS], _env: &Env) {
+        if let Some(t) = self.advance() {
+            self.draw(led_data, t);
+        }
+    }
+
+    // the fades end up in the output stage without going through 8 bit
+    fn tick16(&mut self, led_data: &mut [RGB16; Matrix::NUM_LEDS], _env: &Env) {
+        if let Some(t) = self.advance() {
+            self.draw(led_data, t);
+        }
+    }
 }
@@ -0,0 +1,51 @@
+use crate::font::{self, Font};
+use crate::prelude::*;
+
+// frames per half cell scroll step
+const STEP_FRAMES: u32 = 3;
+
+pub struct Marquee {
+    text: &'static str,
+    font: &'static Font,
+    // horizontal text position in half cells
+    x_half: i32,
+    i: u32,
+    hue: u8,
+}
+
+pub fn new(text: &'static str) -> Marquee {
+    with_font(text, &font::FONT_5X7)
+}
+
+pub fn with_font(text: &'static str, font: &'static Font) -> Marquee {
+    Marquee {
+        text,
+        font,
+        x_half: 2 * Matrix::WIDTH as i32,
+        i: 0,
+        hue: 0,
+    }
+}
+
+impl app::App for Marquee {
+    fn tick(&mut self, canvas: &mut [RGB8; Matrix::NUM_LEDS], _env: &Env) {
+        canvas.clear();
+        let y = (Matrix::HEIGHT - self.font.height) as i32 / 2;
+        let color = (&HV8 {
+            h: self.hue,
+            v: 160,
+        })
+            .into();
+        font::draw_text(canvas, self.font, self.text, self.x_half, y, color);
+
+        self.i = self.i.wrapping_add(1);
+        if self.i.is_multiple_of(STEP_FRAMES) {
+            self.x_half -= 1;
+            self.hue = self.hue.wrapping_add(1);
+        }
+        // restart from the right edge once the text has left the matrix
+        if self.x_half < -2 * self.font.text_width(self.text) as i32 {
+            self.x_half = 2 * Matrix::WIDTH as i32;
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use crate::prelude::*;
+use crate::{color::RGB16, prelude::*};
 
-pub mod drawing;
-// pub mod hexlife;
 pub mod cellular;
+pub mod drawing;
+pub mod hexlife;
 pub mod hexlife2;
+pub mod marquee;
+pub mod playback;
 pub mod power;
+pub mod vu;
 
 #[derive(Default, Clone)]
 pub struct Env {
@@ -13,5 +16,13 @@ pub struct Env {
 
 pub trait App {
     // fn new() -> Self;
-    fn tick(&mut self, led_data: &mut [RGB8; NUM_LEDS], env: &Env);
+    fn tick(&mut self, led_data: &mut [RGB8; Matrix::NUM_LEDS], env: &Env);
+    // Render with 16 bit per channel, for smooth fades at low brightness (the output stage
+    // dithers down to 8 bit). By default this goes through tick, which is lossless as long
+    // as led_data only ever held what tick rendered.
+    fn tick16(&mut self, led_data: &mut [RGB16; Matrix::NUM_LEDS], env: &Env) {
+        let mut data = led_data.map(color::to_rgb8);
+        self.tick(&mut data, env);
+        *led_data = data.map(color::to_rgb16);
+    }
 }
@@ -0,0 +1,36 @@
+use crate::frames::Frames;
+use crate::prelude::*;
+
+// rgb_task ticks apps every 16ms
+const TICK_MS: u32 = 16;
+
+// Plays a frame sequence produced by tools/hexconv, e.g.
+// app::playback::new(include_bytes!("logo.hxf"))
+pub struct Playback {
+    frames: Frames<'static>,
+    i: usize,
+    elapsed_ms: u32,
+}
+
+pub fn new(data: &'static [u8]) -> Result<Playback, crate::frames::Error> {
+    Ok(Playback {
+        frames: Frames::new(data)?,
+        i: 0,
+        elapsed_ms: 0,
+    })
+}
+
+impl app::App for Playback {
+    fn tick(&mut self, led_data: &mut [RGB8; Matrix::NUM_LEDS], _env: &Env) {
+        let Some(frame) = self.frames.get(self.i) else {
+            return;
+        };
+        frame.write_to(led_data);
+        self.elapsed_ms += TICK_MS;
+        // delay 0 (e.g. single still images) holds the frame
+        if frame.delay_ms != 0 && self.elapsed_ms >= frame.delay_ms as u32 {
+            self.elapsed_ms = 0;
+            self.i = (self.i + 1) % self.frames.len();
+        }
+    }
+}
@@ -2,21 +2,21 @@ use crate::prelude::*;
 
 pub struct Power {
     i: usize,
-    on: [bool; NUM_LEDS],
+    on: [bool; Matrix::NUM_LEDS],
 }
 
 pub fn new() -> Power {
     Power {
         i: 0,
-        on: [false; NUM_LEDS],
+        on: [false; Matrix::NUM_LEDS],
     }
 }
 
 impl app::App for Power {
-    fn tick(&mut self, canvas: &mut [RGB8; NUM_LEDS], _env: &Env) {
+    fn tick(&mut self, canvas: &mut [RGB8; Matrix::NUM_LEDS], _env: &Env) {
         // canvas.fill(color::BLACK);
-        let i = self.i % NUM_LEDS;
-        // for i in 0..(self.i % NUM_LEDS) {
+        let i = self.i % Matrix::NUM_LEDS;
+        // for i in 0..(self.i % Matrix::NUM_LEDS) {
         if !self.on[i] {
             canvas[i] = color::WHITE;
         } else {
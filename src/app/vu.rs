@@ -0,0 +1,33 @@
+use crate::{color::BlendMode, matrix, prelude::*};
+
+// Sound level meter: rows light up from the bottom with env.spl_db, green to red. All other
+// leds stay black, so it can be put on top of another app, see compositor.
+
+const DB_MIN: f32 = 45.0;
+const DB_MAX: f32 = 100.0;
+// fall back per frame, in fractions of the full height
+const DECAY: f32 = 0.01;
+
+pub struct Vu {
+    level: f32,
+}
+
+pub fn new() -> Vu {
+    Vu { level: 0.0 }
+}
+
+impl app::App for Vu {
+    fn tick(&mut self, canvas: &mut [RGB8; Matrix::NUM_LEDS], env: &Env) {
+        let target = ((env.spl_db - DB_MIN) / (DB_MAX - DB_MIN)).clamp(0.0, 1.0);
+        self.level = target.max(self.level - DECAY);
+        let rows = (self.level * Matrix::HEIGHT as f32) as usize;
+        canvas.clear();
+        for (i, _, y) in matrix::leds_xy() {
+            let from_bottom = Matrix::HEIGHT - 1 - y;
+            if from_bottom < rows {
+                let t = (from_bottom * 255 / (Matrix::HEIGHT - 1)) as u8;
+                canvas[i] = color::blend(color::GREEN, color::RED, BlendMode::Alpha(t));
+            }
+        }
+    }
+}
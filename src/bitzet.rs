@@ -1,6 +1,9 @@
-use core::iter::FromIterator;
+use core::{
+    iter::FromIterator,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Sub, SubAssign},
+};
 
-use crate::math::Vec2;
+use crate::{hex::Cube, math::Vec2};
 use bitset_core::BitSet;
 //
 // bit-set indexed by Vec2 type. Basically meant as a no_std drop in replacement for
@@ -29,32 +32,148 @@ impl<'a, const N: usize> Iterator for ZOrderIterator<'a, N> {
     type Item = Vec2;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            if self.quadrant >= 4 {
-                return None;
+        while self.quadrant < 4 {
+            let q = self.quadrant;
+            if let Some(z) = self.s.next_bit(q, self.z, self.s.max[q] + 1) {
+                self.z = z + 1;
+                return Some(quadrant_point(q, z));
             }
-            if self.z > self.s.max[self.quadrant] {
-                self.quadrant += 1;
-                self.z = 0;
-                continue;
+            self.quadrant += 1;
+            self.z = 0;
+        }
+        None
+    }
+}
+
+// Pending z-order blocks of RegionIterator. Each split replaces one block by four, so the
+// stack holds at most three siblings per level below the root (16 levels at most).
+const REGION_STACK: usize = 3 * 16 + 4;
+
+// Points inside of a region, in z-order per quadrant. The bounding box of the region is
+// used to prune whole blocks of the z-order curve (a block of 4^k consecutive z-indices is
+// a square of 2^k x 2^k points), only blocks partially inside of it are split further.
+// Points in the remaining blocks are read word by word and checked with contains.
+pub struct RegionIterator<'a, const N: usize, F> {
+    s: &'a Bitzet<N>,
+    min: Vec2,
+    max: Vec2,
+    contains: F,
+    quadrant: usize,
+    // (first z-index, level) of the blocks still to visit
+    stack: [(usize, u32); REGION_STACK],
+    len: usize,
+    // z-index range currently scanned
+    z: usize,
+    end: usize,
+}
+
+impl<'a, const N: usize, F: Fn(&Vec2) -> bool> RegionIterator<'a, N, F> {
+    fn new(s: &'a Bitzet<N>, min: Vec2, max: Vec2, contains: F) -> Self {
+        let mut it = RegionIterator {
+            s,
+            min,
+            max,
+            contains,
+            quadrant: 0,
+            stack: [(0, 0); REGION_STACK],
+            len: 0,
+            z: 0,
+            end: 0,
+        };
+        it.push_root();
+        it
+    }
+
+    // level of the block covering all N * 32 bits of a quadrant
+    const ROOT: u32 = {
+        let mut level = 0;
+        while (1usize << (2 * level)) < N * 32 {
+            level += 1;
+        }
+        level
+    };
+
+    fn push_root(&mut self) {
+        self.stack[0] = (0, Self::ROOT);
+        self.len = 1;
+    }
+
+    // bounding box of the region in the current quadrant, as absolute coordinates (like
+    // zorder_abs), None if they do not overlap
+    fn quadrant_box(&self) -> Option<(Vec2, Vec2)> {
+        let axis = |min: i32, max: i32, neg: bool| {
+            let (lo, hi) = if neg {
+                (-max.min(-1), -min)
+            } else {
+                (min.max(0), max)
+            };
+            (lo <= hi).then_some((lo, hi))
+        };
+        let (x0, x1) = axis(self.min.x, self.max.x, self.quadrant & 1 != 0)?;
+        let (y0, y1) = axis(self.min.y, self.max.y, self.quadrant & 2 != 0)?;
+        Some((Vec2::new(x0, y0), Vec2::new(x1, y1)))
+    }
+
+    // Take the next block off the stack: skip it, split it or start scanning it.
+    fn visit(&mut self, (lo, hi): (Vec2, Vec2)) {
+        self.len -= 1;
+        let (z, level) = self.stack[self.len];
+        let end = self.s.max[self.quadrant] + 1;
+        if z >= end {
+            return;
+        }
+        let corner = zorder_inverse(z as u32);
+        let size = 1 << level;
+        let (x0, y0) = (corner.x, corner.y);
+        let (x1, y1) = (x0 + size - 1, y0 + size - 1);
+        if x1 < lo.x || x0 > hi.x || y1 < lo.y || y0 > hi.y {
+            return;
+        }
+        let inside = x0 >= lo.x && x1 <= hi.x && y0 >= lo.y && y1 <= hi.y;
+        // below 32 points splitting costs more than scanning
+        if inside || level <= 2 {
+            self.z = z;
+            self.end = (z + (1 << (2 * level))).min(end);
+            return;
+        }
+        // children in reverse, so they come off the stack in z-order
+        let child = 1 << (2 * (level - 1));
+        for i in (0..4).rev() {
+            self.stack[self.len] = (z + i * child, level - 1);
+            self.len += 1;
+        }
+    }
+}
+
+impl<'a, const N: usize, F: Fn(&Vec2) -> bool> Iterator for RegionIterator<'a, N, F> {
+    type Item = Vec2;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.quadrant < 4 {
+            let q = self.quadrant;
+            while let Some(z) = self.s.next_bit(q, self.z, self.end) {
+                self.z = z + 1;
+                let v = quadrant_point(q, z);
+                let in_box = (self.min.x..=self.max.x).contains(&v.x)
+                    && (self.min.y..=self.max.y).contains(&v.y);
+                if in_box && (self.contains)(&v) {
+                    return Some(v);
+                }
             }
-            if self.s.quadrants[self.quadrant].bit_test(self.z) {
-                break;
+            self.z = self.end;
+            match self.quadrant_box() {
+                Some(bounds) if self.len > 0 => self.visit(bounds),
+                _ => {
+                    self.quadrant += 1;
+                    self.push_root();
+                }
             }
-            self.z += 1;
-        }
-        let ret = zorder_inverse(self.z as u32);
-        self.z += 1;
-        match self.quadrant {
-            0 => Some(Vec2::new(ret.x, ret.y)),
-            1 => Some(Vec2::new(-ret.x, ret.y)),
-            2 => Some(Vec2::new(ret.x, -ret.y)),
-            3 => Some(Vec2::new(-ret.x, -ret.y)),
-            _ => None,
         }
+        None
     }
 }
 
+// panics if a point is out of capacity, like insert
 impl<const N: usize> FromIterator<Vec2> for Bitzet<N> {
     fn from_iter<T: IntoIterator<Item = Vec2>>(iter: T) -> Self {
         let mut bz = Bitzet::new();
@@ -68,6 +187,12 @@ impl<const N: usize> Default for Bitzet<N> {
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    // coordinate does not fit into the N words per quadrant
+    OutOfCapacity(Vec2),
+}
+
 impl<const N: usize> Bitzet<N> {
     pub fn new() -> Bitzet<N> {
         Bitzet {
@@ -75,17 +200,55 @@ impl<const N: usize> Bitzet<N> {
             max: [0; 4],
         }
     }
+    // Largest c so that every v with |v.x| <= c and |v.y| <= c can be inserted (some
+    // points further out along the axes fit as well, see try_insert).
+    pub const fn capacity() -> i32 {
+        let mut c = 0;
+        while c < 0xffff && (zorder(c + 1, c + 1) as usize) < N * 32 {
+            c += 1;
+        }
+        c as i32
+    }
+    // panics if v is out of capacity
     pub fn insert(&mut self, v: Vec2) {
-        let z = zorder_abs(&v);
+        self.try_insert(v).expect("bitzet capacity exceeded");
+    }
+    pub fn try_insert(&mut self, v: Vec2) -> Result<(), Error> {
+        let z = zorder_checked::<N>(&v).ok_or(Error::OutOfCapacity(v))?;
         let q = quadrant_index(&v);
         self.quadrants[q].bit_set(z);
         self.max[q] = self.max[q].max(z);
+        Ok(())
     }
+    // no-op for points out of capacity (they can not be in the set)
     pub fn remove(&mut self, v: &Vec2) {
-        self.quadrants[quadrant_index(v)].bit_reset(zorder_abs(v));
+        let Some(z) = zorder_checked::<N>(v) else {
+            return;
+        };
+        let q = quadrant_index(v);
+        self.quadrants[q].bit_reset(z);
+        if z == self.max[q] {
+            self.shrink_max(q);
+        }
+    }
+    // lower max[q] to the highest bit actually set
+    fn shrink_max(&mut self, q: usize) {
+        let mut w = self.max[q] / 32;
+        loop {
+            let word = self.quadrants[q][w];
+            if word != 0 {
+                self.max[q] = w * 32 + 31 - word.leading_zeros() as usize;
+                return;
+            }
+            if w == 0 {
+                self.max[q] = 0;
+                return;
+            }
+            w -= 1;
+        }
     }
     pub fn get(&self, v: &Vec2) -> bool {
-        self.quadrants[quadrant_index(v)].bit_test(zorder_abs(v))
+        zorder_checked::<N>(v).is_some_and(|z| self.quadrants[quadrant_index(v)].bit_test(z))
     }
     pub fn contains(&self, v: &Vec2) -> bool {
         // println!("contains: {:?}", v);
@@ -106,19 +269,76 @@ impl<const N: usize> Bitzet<N> {
         self.len() == 0
     }
 
+    // Set operations only touch the words below the max hints, which stay upper bounds of
+    // the highest set bit in each quadrant.
+    pub fn union(&self, other: &Self) -> Self {
+        let mut out = self.clone();
+        out.union_assign(other);
+        out
+    }
+    pub fn intersection(&self, other: &Self) -> Self {
+        let mut out = self.clone();
+        out.intersection_assign(other);
+        out
+    }
     pub fn difference(&self, other: &Self) -> Self {
-        let mut q0 = self.quadrants[0];
-        q0.bit_andnot(&other.quadrants[0]);
-        let mut q1 = self.quadrants[1];
-        q1.bit_andnot(&other.quadrants[1]);
-        let mut q2 = self.quadrants[2];
-        q2.bit_andnot(&other.quadrants[2]);
-        let mut q3 = self.quadrants[3];
-        q3.bit_andnot(&other.quadrants[3]);
+        let mut out = self.clone();
+        out.difference_assign(other);
+        out
+    }
+    pub fn symmetric_difference(&self, other: &Self) -> Self {
+        let mut out = self.clone();
+        out.symmetric_difference_assign(other);
+        out
+    }
 
-        Bitzet {
-            quadrants: [q0, q1, q2, q3],
-            max: self.max,
+    pub fn union_assign(&mut self, other: &Self) {
+        for q in 0..4 {
+            let n = words(other.max[q]);
+            self.quadrants[q][..n].bit_or(&other.quadrants[q][..n]);
+            self.max[q] = self.max[q].max(other.max[q]);
+        }
+    }
+    pub fn intersection_assign(&mut self, other: &Self) {
+        for q in 0..4 {
+            let max = self.max[q].min(other.max[q]);
+            let n = words(max);
+            self.quadrants[q][..n].bit_and(&other.quadrants[q][..n]);
+            self.quadrants[q][n..words(self.max[q])].fill(0);
+            self.max[q] = max;
+            self.shrink_max(q);
+        }
+    }
+    pub fn difference_assign(&mut self, other: &Self) {
+        for q in 0..4 {
+            let n = words(self.max[q].min(other.max[q]));
+            self.quadrants[q][..n].bit_andnot(&other.quadrants[q][..n]);
+            self.shrink_max(q);
+        }
+    }
+    pub fn symmetric_difference_assign(&mut self, other: &Self) {
+        for q in 0..4 {
+            let n = words(other.max[q]);
+            self.quadrants[q][..n].bit_xor(&other.quadrants[q][..n]);
+            self.max[q] = self.max[q].max(other.max[q]);
+            self.shrink_max(q);
+        }
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        (0..4).all(|q| {
+            let n = words(self.max[q]);
+            self.quadrants[q][..n]
+                .iter()
+                .zip(&other.quadrants[q][..n])
+                .all(|(a, b)| a & !b == 0)
+        })
+    }
+
+    pub fn clear(&mut self) {
+        for q in 0..4 {
+            self.quadrants[q][..words(self.max[q])].fill(0);
+            self.max[q] = 0;
         }
     }
     pub fn iter(&self) -> ZOrderIterator<'_, N> {
@@ -128,6 +348,92 @@ impl<const N: usize> Bitzet<N> {
             z: 0,
         }
     }
+    // points with min.x <= x <= max.x and min.y <= y <= max.y
+    pub fn iter_in_rect(
+        &self,
+        min: Vec2,
+        max: Vec2,
+    ) -> RegionIterator<'_, N, impl Fn(&Vec2) -> bool> {
+        RegionIterator::new(self, min, max, |_: &Vec2| true)
+    }
+    // points within hex distance radius of center (coordinates as in Cube::from_oddr)
+    pub fn iter_in_hex_range(
+        &self,
+        center: Cube,
+        radius: i32,
+    ) -> RegionIterator<'_, N, impl Fn(&Vec2) -> bool> {
+        // the widest row of a hex range is the center row
+        let c = center.to_oddr();
+        let min = Vec2::new(c.x - radius, c.y - radius);
+        let max = Vec2::new(c.x + radius, c.y + radius);
+        RegionIterator::new(self, min, max, move |v: &Vec2| {
+            Cube::from_oddr(*v).distance(&center) <= radius
+        })
+    }
+
+    // first set bit of quadrant q in z-indices start..end, skipping empty words
+    fn next_bit(&self, q: usize, start: usize, end: usize) -> Option<usize> {
+        let end = end.min(N * 32);
+        let mut w = start / 32;
+        let mut word = self.quadrants[q].get(w)? & (!0 << (start % 32));
+        while w * 32 < end {
+            if word != 0 {
+                let z = w * 32 + word.trailing_zeros() as usize;
+                return (z < end).then_some(z);
+            }
+            w += 1;
+            word = *self.quadrants[q].get(w)?;
+        }
+        None
+    }
+}
+impl<const N: usize> BitOr for &Bitzet<N> {
+    type Output = Bitzet<N>;
+    fn bitor(self, rhs: Self) -> Bitzet<N> {
+        self.union(rhs)
+    }
+}
+impl<const N: usize> BitAnd for &Bitzet<N> {
+    type Output = Bitzet<N>;
+    fn bitand(self, rhs: Self) -> Bitzet<N> {
+        self.intersection(rhs)
+    }
+}
+impl<const N: usize> Sub for &Bitzet<N> {
+    type Output = Bitzet<N>;
+    fn sub(self, rhs: Self) -> Bitzet<N> {
+        self.difference(rhs)
+    }
+}
+impl<const N: usize> BitOrAssign<&Bitzet<N>> for Bitzet<N> {
+    fn bitor_assign(&mut self, rhs: &Bitzet<N>) {
+        self.union_assign(rhs);
+    }
+}
+impl<const N: usize> BitAndAssign<&Bitzet<N>> for Bitzet<N> {
+    fn bitand_assign(&mut self, rhs: &Bitzet<N>) {
+        self.intersection_assign(rhs);
+    }
+}
+impl<const N: usize> SubAssign<&Bitzet<N>> for Bitzet<N> {
+    fn sub_assign(&mut self, rhs: &Bitzet<N>) {
+        self.difference_assign(rhs);
+    }
+}
+
+// number of words that can hold bits up to z-index max
+fn words(max: usize) -> usize {
+    max / 32 + 1
+}
+// inverse of quadrant_index + zorder_abs
+fn quadrant_point(q: usize, z: usize) -> Vec2 {
+    let v = zorder_inverse(z as u32);
+    match q {
+        0 => Vec2::new(v.x, v.y),
+        1 => Vec2::new(-v.x, v.y),
+        2 => Vec2::new(v.x, -v.y),
+        _ => Vec2::new(-v.x, -v.y),
+    }
 }
 fn quadrant_index(v: &Vec2) -> usize {
     let xneg = if v.x < 0 { 1 } else { 0 };
@@ -137,7 +443,15 @@ fn quadrant_index(v: &Vec2) -> usize {
 fn zorder_abs(v: &Vec2) -> usize {
     zorder(v.x.unsigned_abs(), v.y.unsigned_abs()) as usize
 }
-fn zorder(mut x: u32, mut y: u32) -> u32 {
+// zorder_abs if it fits into N words (zorder only uses the lower 16 bits of x and y)
+fn zorder_checked<const N: usize>(v: &Vec2) -> Option<usize> {
+    if v.x.unsigned_abs() > 0xffff || v.y.unsigned_abs() > 0xffff {
+        return None;
+    }
+    let z = zorder_abs(v);
+    (z < N * 32).then_some(z)
+}
+const fn zorder(mut x: u32, mut y: u32) -> u32 {
     // from https://graphics.stanford.edu/~seander/bithacks.html
 
     const B: [u32; 4] = [0x55555555, 0x33333333, 0x0F0F0F0F, 0x00FF00FF];
@@ -275,3 +589,187 @@ fn zorder_inverse(z: u32) -> Vec2 {
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+    use std::{collections::HashSet, vec::Vec};
+
+    type BitzetN = Bitzet<128>;
+
+    // up to n random points within |x|, |y| <= extent, in all four quadrants
+    fn random_points(rng: &mut SmallRng, n: usize, extent: i32) -> Vec<Vec2> {
+        let n = rng.gen_range(0..=n);
+        (0..n)
+            .map(|_| {
+                Vec2::new(
+                    rng.gen_range(-extent..=extent),
+                    rng.gen_range(-extent..=extent),
+                )
+            })
+            .collect()
+    }
+
+    fn to_set(s: &BitzetN) -> HashSet<Vec2> {
+        let set = s.iter().collect::<HashSet<_>>();
+        assert_eq!(set.len(), s.len());
+        set
+    }
+
+    #[test]
+    fn set_algebra_matches_hash_set() {
+        let mut rng = SmallRng::seed_from_u64(42);
+        for round in 0..300 {
+            // small and large extents, so the max hints of the two sets differ
+            let (ea, eb) = [(3, 3), (3, 30), (30, 3), (30, 30)][round % 4];
+            let a = random_points(&mut rng, 80, ea);
+            let b = random_points(&mut rng, 80, eb);
+            let (za, zb) = (
+                a.iter().copied().collect::<BitzetN>(),
+                b.iter().copied().collect::<BitzetN>(),
+            );
+            let (ha, hb) = (
+                a.iter().copied().collect::<HashSet<_>>(),
+                b.iter().copied().collect::<HashSet<_>>(),
+            );
+            assert_eq!(to_set(&za), ha);
+
+            let union = ha.union(&hb).copied().collect::<HashSet<_>>();
+            let intersection = ha.intersection(&hb).copied().collect::<HashSet<_>>();
+            let difference = ha.difference(&hb).copied().collect::<HashSet<_>>();
+            let symmetric = ha
+                .symmetric_difference(&hb)
+                .copied()
+                .collect::<HashSet<_>>();
+            assert_eq!(to_set(&za.union(&zb)), union);
+            assert_eq!(to_set(&za.intersection(&zb)), intersection);
+            assert_eq!(to_set(&za.difference(&zb)), difference);
+            assert_eq!(to_set(&za.symmetric_difference(&zb)), symmetric);
+            assert_eq!(za.is_subset(&zb), ha.is_subset(&hb));
+            assert!(za.intersection(&zb).is_subset(&za));
+            assert!(za.is_subset(&za.union(&zb)));
+            assert_eq!(za.is_empty(), ha.is_empty());
+
+            assert_eq!(to_set(&(&za | &zb)), union);
+            assert_eq!(to_set(&(&za & &zb)), intersection);
+            assert_eq!(to_set(&(&za - &zb)), difference);
+            let mut z = za.clone();
+            z |= &zb;
+            assert_eq!(to_set(&z), union);
+            let mut z = za.clone();
+            z &= &zb;
+            assert_eq!(to_set(&z), intersection);
+            let mut z = za.clone();
+            z -= &zb;
+            assert_eq!(to_set(&z), difference);
+
+            // the results keep working as sets after shrinking their max hints
+            let mut z = za.symmetric_difference(&zb);
+            for v in &b {
+                z.remove(v);
+            }
+            let expected = symmetric.difference(&hb).copied().collect::<HashSet<_>>();
+            assert_eq!(to_set(&z), expected);
+            z.clear();
+            assert!(z.is_empty());
+        }
+    }
+
+    // every point within capacity() fits, the next diagonal one does not
+    fn check_capacity<const N: usize>(expected: i32) {
+        let c = Bitzet::<N>::capacity();
+        assert_eq!(c, expected);
+        let mut s = Bitzet::<N>::new();
+        for y in -c..=c {
+            for x in -c..=c {
+                assert_eq!(s.try_insert(Vec2::new(x, y)), Ok(()));
+            }
+        }
+        assert_eq!(s.len(), ((2 * c + 1) * (2 * c + 1)) as usize);
+        for v in
+            [(1, 1), (-1, 1), (1, -1), (-1, -1)].map(|(x, y)| Vec2::new(x * (c + 1), y * (c + 1)))
+        {
+            assert_eq!(s.try_insert(v), Err(Error::OutOfCapacity(v)));
+            assert!(!s.contains(&v));
+            s.remove(&v);
+        }
+        assert_eq!(s.len(), ((2 * c + 1) * (2 * c + 1)) as usize);
+    }
+
+    #[test]
+    fn capacity() {
+        check_capacity::<1>(3);
+        check_capacity::<2>(7);
+        check_capacity::<3>(7);
+        check_capacity::<128>(63);
+        // along the axes a few more points fit when N is not a power of 4
+        let mut s = Bitzet::<3>::new();
+        assert_eq!(s.try_insert(Vec2::new(-8, 0)), Ok(()));
+        assert!(s.contains(&Vec2::new(-8, 0)));
+        // beyond the 16 bits zorder looks at
+        let v = Vec2::new(0x10000, 0);
+        assert_eq!(
+            Bitzet::<128>::new().try_insert(v),
+            Err(Error::OutOfCapacity(v))
+        );
+    }
+
+    #[test]
+    fn regions_match_filter() {
+        let mut rng = SmallRng::seed_from_u64(3);
+        let c = BitzetN::capacity();
+        let centers = [
+            (0, 0),
+            (5, -3),
+            (-7, -7),
+            (-20, 11),
+            (c, c),
+            (-c, 0),
+            (c - 2, -c),
+            (c + 5, 0),
+        ];
+        for round in 0..40 {
+            let points = random_points(&mut rng, 400, c);
+            let s = points.iter().copied().collect::<BitzetN>();
+            for (x, y) in centers {
+                let center = Vec2::new(x, y);
+                let size = [0, 1, 3, 10, 40][round % 5];
+                let (min, max) = (
+                    Vec2::new(x - size, y - size / 2),
+                    Vec2::new(x + size / 2, y + size),
+                );
+                let rect = s.iter_in_rect(min, max).collect::<HashSet<_>>();
+                let expected = s
+                    .iter()
+                    .filter(|v| (min.x..=max.x).contains(&v.x) && (min.y..=max.y).contains(&v.y))
+                    .collect::<HashSet<_>>();
+                assert_eq!(rect, expected, "{:?} {:?}", min, max);
+                assert_eq!(s.iter_in_rect(min, max).count(), expected.len());
+
+                let cube = Cube::from_oddr(center);
+                let range = s.iter_in_hex_range(cube, size).collect::<HashSet<_>>();
+                let expected = s
+                    .iter()
+                    .filter(|v| Cube::from_oddr(*v).distance(&cube) <= size)
+                    .collect::<HashSet<_>>();
+                assert_eq!(range, expected, "{:?} {}", center, size);
+            }
+        }
+        // empty and inverted rectangles
+        let s = [Vec2::new(1, 1)].into_iter().collect::<BitzetN>();
+        assert_eq!(s.iter_in_rect(Vec2::new(2, 2), Vec2::new(1, 1)).count(), 0);
+        assert_eq!(
+            BitzetN::new()
+                .iter_in_rect(Vec2::new(-c, -c), Vec2::new(c, c))
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "bitzet capacity exceeded")]
+    fn insert_out_of_capacity() {
+        Bitzet::<128>::new().insert(Vec2::new(0, 64));
+    }
+}
@@ -1,29 +1,506 @@
+use bitset_core::BitSet;
+use num_traits::float::FloatCore;
 use smart_leds::RGB8;
 
-use crate::color;
+use crate::color::{self, BlendMode, RGB16};
+use crate::sprite::Sprite;
 
-use super::hex;
+use super::hex::{self, Neighbors};
 use crate::prelude::*;
 
+// Only clear / set_oddr / get_oddr need to be implemented, all drawing primitives are
+// built on top of those. This also means every primitive can be drawn with a blend mode
+// through the Blended adapter, e.g. canvas.blended(BlendMode::Add).disc(...)
 pub trait Canvas {
     fn clear(&mut self);
-    fn line(&mut self, a: hex::Cube, b: hex::Cube, color: RGB8);
     fn set_oddr(&mut self, v: Vec2, color: RGB8);
+    // None for coordinates outside of the matrix
+    fn get_oddr(&self, v: Vec2) -> Option<RGB8>;
     // fn apply(&mut self);
-    // fn data(&mut self) -> &mut [RGB8; NUM_LEDS];
-}
+    // fn data(&mut self) -> &mut [RGB8; Matrix::NUM_LEDS];
 
-impl Canvas for [RGB8; NUM_LEDS] {
+    fn blended(&mut self, mode: BlendMode) -> Blended<'_, Self>
+    where
+        Self: Sized,
+    {
+        Blended { canvas: self, mode }
+    }
+    fn blend_oddr(&mut self, v: Vec2, color: RGB8, mode: BlendMode) {
+        if let Some(dst) = self.get_oddr(v) {
+            self.set_oddr(v, color::blend(dst, color, mode));
+        }
+    }
+    // blend color scaled by weight (0..=65535), canvases with more than 8 bit per channel
+    // keep the low bits of faint weights
+    fn blend_oddr_weighted(&mut self, v: Vec2, color: RGB8, weight: u16, mode: BlendMode) {
+        self.blend_oddr(v, color::scale(color, (weight >> 8) as u8), mode);
+    }
+    fn set_cube(&mut self, c: hex::Cube, color: RGB8) {
+        self.set_oddr(c.into(), color)
+    }
     fn line(&mut self, a: hex::Cube, b: hex::Cube, color: RGB8) {
         for c in hex::CubeLinedraw::new(a, b) {
-            set_matrix_oddr(c.into(), color, self);
+            self.set_cube(c, color);
+        }
+    }
+    fn cells<I: IntoIterator<Item = hex::Cube>>(&mut self, cells: I, color: RGB8)
+    where
+        Self: Sized,
+    {
+        for c in cells {
+            self.set_cube(c, color);
+        }
+    }
+    // ring / spiral double as hexagon outline / filled hexagon of the given radius
+    fn ring(&mut self, center: hex::Cube, radius: i32, color: RGB8) {
+        for c in hex::Ring::new(center, radius) {
+            self.set_cube(c, color);
+        }
+    }
+    fn spiral(&mut self, center: hex::Cube, radius: i32, color: RGB8) {
+        for c in hex::Spiral::new(center, radius) {
+            self.set_cube(c, color);
         }
     }
+    // euclidean circle / disc, radius measured in cell spacings
+    fn circle(&mut self, center: hex::Cube, radius: f32, color: RGB8) {
+        // boundary cells of the disc: inside, but with at least one neighbor outside
+        for c in disc_cells(center, radius) {
+            if (0..6).any(|d| !in_disc(center, radius, &c.neighbor(d))) {
+                self.set_cube(c, color);
+            }
+        }
+    }
+    fn disc(&mut self, center: hex::Cube, radius: f32, color: RGB8) {
+        for c in disc_cells(center, radius) {
+            self.set_cube(c, color);
+        }
+    }
+    fn triangle(&mut self, a: hex::Cube, b: hex::Cube, c: hex::Cube, color: RGB8) {
+        self.polygon(&[a, b, c], color);
+    }
+    fn fill_triangle(&mut self, a: hex::Cube, b: hex::Cube, c: hex::Cube, color: RGB8) {
+        self.fill_polygon(&[a, b, c], color);
+    }
+    // Closed outline through all points. Every cell is drawn once (matters when blending):
+    // CubeLinedraw stops short of the end point, which is drawn as start of the next edge.
+    fn polygon(&mut self, points: &[hex::Cube], color: RGB8) {
+        match points {
+            [] => return,
+            [p] => return self.set_cube(*p, color),
+            // there and back would draw the inner cells twice
+            [a, b] => {
+                self.line(*a, *b, color);
+                return self.set_cube(*b, color);
+            }
+            _ => {}
+        }
+        for (i, a) in points.iter().enumerate() {
+            self.line(*a, points[(i + 1) % points.len()], color);
+        }
+    }
+    // points must describe a convex polygon (either winding order)
+    fn fill_polygon(&mut self, points: &[hex::Cube], color: RGB8) {
+        if points.is_empty() {
+            return;
+        }
+        // axial (q, r) is an affine image of cartesian space, so the usual
+        // half-plane tests work directly on the integer coordinates.
+        let (mut qmin, mut qmax, mut rmin, mut rmax) = (i32::MAX, i32::MIN, i32::MAX, i32::MIN);
+        for p in points {
+            qmin = qmin.min(p.x);
+            qmax = qmax.max(p.x);
+            rmin = rmin.min(p.z);
+            rmax = rmax.max(p.z);
+        }
+        // cells outside of the matrix are rejected by set_oddr, after the display transform
+        for r in rmin..=rmax {
+            for q in qmin..=qmax {
+                if in_convex_polygon(points, q, r) {
+                    self.set_cube(hex::Axial { q, r }.into(), color);
+                }
+            }
+        }
+    }
+    // anti-aliased point at a fractional axial position, intensity is spread over the up to
+    // three nearest cells and added to what is already there
+    fn point_aa_axial(&mut self, q: f32, r: f32, color: RGB8) {
+        for (c, w) in hex::axial_split(q, r) {
+            if w > 0.0 {
+                self.blend_oddr_weighted(c.into(), color, (w * 65535.0) as u16, BlendMode::Add);
+            }
+        }
+    }
+    fn point_aa_cube(&mut self, x: f32, _y: f32, z: f32, color: RGB8) {
+        self.point_aa_axial(x, z, color);
+    }
+    // pixel space as defined by Cube::to_pixel (cell spacing 1.0, origin at Cube::zero)
+    fn point_aa(&mut self, x: f32, y: f32, color: RGB8) {
+        let (q, r) = hex::pixel_to_axial(x, y);
+        self.point_aa_axial(q, r, color);
+    }
+    // Wu line in pixel space. Runs in axial space, where the cells form a plain lattice:
+    // every step along the major axis covers the two cells around the line on the minor
+    // axis, weighted by their distance from it.
+    fn line_aa(&mut self, a: (f32, f32), b: (f32, f32), color: RGB8) {
+        let (a, b) = (hex::pixel_to_axial(a.0, a.1), hex::pixel_to_axial(b.0, b.1));
+        let steep = (b.1 - a.1).abs() > (b.0 - a.0).abs();
+        let major_first = |p: (f32, f32)| if steep { (p.1, p.0) } else { p };
+        let (mut a, mut b) = (major_first(a), major_first(b));
+        if a.0 > b.0 {
+            core::mem::swap(&mut a, &mut b);
+        }
+        let gradient = if b.0 > a.0 {
+            (b.1 - a.1) / (b.0 - a.0)
+        } else {
+            0.0
+        };
+        for major in FloatCore::round(a.0) as i32..=FloatCore::round(b.0) as i32 {
+            let minor = a.1 + gradient * (major as f32 - a.0);
+            let m = FloatCore::floor(minor);
+            let f = minor - m;
+            for (minor, w) in [(m as i32, 1.0 - f), (m as i32 + 1, f)] {
+                let (q, r) = if steep {
+                    (minor, major)
+                } else {
+                    (major, minor)
+                };
+                if w > 0.0 {
+                    let c = hex::Axial { q, r }.into();
+                    self.blend_oddr_weighted(c, color, (w * 65535.0) as u16, BlendMode::Add);
+                }
+            }
+        }
+    }
+    // draw sprite centered at pos, rotated clockwise by rotation * 60° (after mirroring)
+    fn blit(&mut self, sprite: &Sprite, pos: hex::Cube, rotation: i32, mirror: bool) {
+        for (c, color) in sprite.iter() {
+            let c = if mirror { c.mirror() } else { c };
+            self.set_cube(pos + c.rotate(rotation), color);
+        }
+    }
+    // Replace the connected area of same-colored leds around start. Scanline fill: whole
+    // row spans are filled at once and only the first cell of every run of matching cells
+    // next to them is queued. Seeds which do not fit into the queue are found again by
+    // looking for matching leds next to filled ones.
+    fn flood_fill(&mut self, start: hex::Cube, color: RGB8) {
+        let start: Vec2 = start.into();
+        let Some(target) = self.get_oddr(start) else {
+            return;
+        };
+        if target == color {
+            return;
+        }
+        let mut filled = [0u32; Matrix::NUM_LEDS / 32 + 1];
+        let mut seeds = [start; 16];
+        let mut n = 1;
+        let mut dropped = false;
+        loop {
+            while n > 0 {
+                n -= 1;
+                let v = seeds[n];
+                if !fillable(self, &filled, v, target) {
+                    continue;
+                }
+                let (mut x0, mut x1) = (v.x, v.x);
+                while fillable(self, &filled, Vec2::new(x0 - 1, v.y), target) {
+                    x0 -= 1;
+                }
+                while fillable(self, &filled, Vec2::new(x1 + 1, v.y), target) {
+                    x1 += 1;
+                }
+                for x in x0..=x1 {
+                    let c = Vec2::new(x, v.y);
+                    if let Ok(addr) = led_addr_oddr(c) {
+                        filled.bit_set(addr);
+                    }
+                    self.set_oddr(c, color);
+                }
+                // cells of the rows above and below touching the span
+                let shift = v.y.abs() % 2;
+                for y in [v.y - 1, v.y + 1] {
+                    let mut in_run = false;
+                    for x in x0 - 1 + shift..=x1 + shift {
+                        let c = Vec2::new(x, y);
+                        let fill = fillable(self, &filled, c, target);
+                        if fill && !in_run {
+                            if n < seeds.len() {
+                                seeds[n] = c;
+                                n += 1;
+                            } else {
+                                dropped = true;
+                            }
+                        }
+                        in_run = fill;
+                    }
+                }
+            }
+            if !dropped {
+                return;
+            }
+            dropped = false;
+            for (_, v) in crate::matrix::leds_oddr() {
+                let next_to_filled = || {
+                    v.neighbors()
+                        .iter()
+                        .any(|n| led_addr_oddr(*n).is_ok_and(|addr| filled.bit_test(addr)))
+                };
+                if fillable(self, &filled, v, target) && next_to_filled() {
+                    if n < seeds.len() {
+                        seeds[n] = v;
+                        n += 1;
+                    } else {
+                        dropped = true;
+                    }
+                }
+            }
+        }
+    }
+}
 
+impl Canvas for [RGB8; Matrix::NUM_LEDS] {
     fn clear(&mut self) {
         self.fill(color::BLACK);
     }
     fn set_oddr(&mut self, v: Vec2, color: RGB8) {
         set_matrix_oddr(v, color, self)
     }
+    fn get_oddr(&self, v: Vec2) -> Option<RGB8> {
+        led_addr_oddr(v).ok().map(|addr| self[addr])
+    }
+}
+
+// Colors are drawn with 8 bit precision, blending and weights keep all 16 bits.
+impl Canvas for [RGB16; Matrix::NUM_LEDS] {
+    fn clear(&mut self) {
+        self.fill(RGB16::default());
+    }
+    fn set_oddr(&mut self, v: Vec2, color: RGB8) {
+        if let Ok(addr) = led_addr_oddr(v) {
+            self[addr] = color::to_rgb16(color);
+        }
+    }
+    fn get_oddr(&self, v: Vec2) -> Option<RGB8> {
+        led_addr_oddr(v).ok().map(|addr| color::to_rgb8(self[addr]))
+    }
+    fn blend_oddr(&mut self, v: Vec2, color: RGB8, mode: BlendMode) {
+        self.blend_oddr_weighted(v, color, u16::MAX, mode);
+    }
+    fn blend_oddr_weighted(&mut self, v: Vec2, color: RGB8, weight: u16, mode: BlendMode) {
+        if let Ok(addr) = led_addr_oddr(v) {
+            let scale = |c: u8| ((c as u32 * 257 * (weight as u32 + 1)) >> 16) as u16;
+            let src = RGB16::new(scale(color.r), scale(color.g), scale(color.b));
+            self[addr] = color::blend16(self[addr], src, mode);
+        }
+    }
+}
+
+// Canvas adapter that blends everything drawn through it onto the underlying canvas
+pub struct Blended<'a, C: Canvas> {
+    canvas: &'a mut C,
+    mode: BlendMode,
+}
+
+impl<C: Canvas> Canvas for Blended<'_, C> {
+    fn clear(&mut self) {
+        self.canvas.clear();
+    }
+    fn set_oddr(&mut self, v: Vec2, color: RGB8) {
+        self.canvas.blend_oddr(v, color, self.mode);
+    }
+    fn get_oddr(&self, v: Vec2) -> Option<RGB8> {
+        self.canvas.get_oddr(v)
+    }
+}
+
+fn in_disc(center: hex::Cube, radius: f32, c: &hex::Cube) -> bool {
+    let (x, y) = (*c - center).to_pixel();
+    x * x + y * y <= radius * radius
+}
+
+fn disc_cells(center: hex::Cube, radius: f32) -> impl Iterator<Item = hex::Cube> {
+    // hex distance overestimates the euclidean one by at most 2/sqrt(3)
+    let hex_radius = (radius * 1.155) as i32 + 1;
+    hex::Spiral::new(center, hex_radius).filter(move |c| in_disc(center, radius, c))
+}
+
+// led at v not filled yet and of the color being replaced
+fn fillable<C: Canvas + ?Sized>(canvas: &C, filled: &[u32], v: Vec2, target: RGB8) -> bool {
+    match led_addr_oddr(v) {
+        Ok(addr) => !filled.bit_test(addr) && canvas.get_oddr(v) == Some(target),
+        Err(_) => false,
+    }
+}
+
+fn in_convex_polygon(points: &[hex::Cube], q: i32, r: i32) -> bool {
+    let mut pos = false;
+    let mut neg = false;
+    for (i, a) in points.iter().enumerate() {
+        let b = &points[(i + 1) % points.len()];
+        let cross = (b.x - a.x) * (r - a.z) - (b.z - a.z) * (q - a.x);
+        pos |= cross > 0;
+        neg |= cross < 0;
+    }
+    !(pos && neg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn polygon_draws_every_cell_once() {
+        let _lock = crate::matrix::lock_transform();
+        let mut canvas = [color::BLACK; Matrix::NUM_LEDS];
+        let corners = [(0, 0), (4, 0), (0, 4)].map(|(q, r)| hex::Cube::from(hex::Axial { q, r }));
+        canvas
+            .blended(BlendMode::Add)
+            .polygon(&corners, RGB8::new(1, 0, 0));
+        let outline: std::collections::HashSet<_> = (0..3)
+            .flat_map(|i| hex::CubeLinedraw::new(corners[i], corners[(i + 1) % 3]))
+            .collect();
+        assert_eq!(outline.len(), 12);
+        assert!(canvas.iter().all(|c| c.r <= 1));
+        // only the part of the outline covered by the layout shows up
+        let on_matrix = outline
+            .iter()
+            .filter(|c| led_addr_oddr((**c).into()).is_ok())
+            .count();
+        assert_eq!(canvas.iter().filter(|c| c.r == 1).count(), on_matrix);
+        for c in outline {
+            if led_addr_oddr(c.into()).is_ok() {
+                assert_eq!(canvas.get_oddr(c.into()), Some(RGB8::new(1, 0, 0)));
+            }
+        }
+
+        let mut canvas = [color::BLACK; Matrix::NUM_LEDS];
+        let p = crate::matrix::LED_CUBES[0];
+        canvas.polygon(&[p], color::RED);
+        assert_eq!(canvas.get_oddr(p.into()), Some(color::RED));
+
+        // a single line, drawn once including both ends
+        let mut canvas = [color::BLACK; Matrix::NUM_LEDS];
+        // any line of a few cells which stays on the matrix
+        let leds = crate::matrix::LED_CUBES;
+        let on_matrix = |a: hex::Cube, b: hex::Cube| {
+            hex::CubeLinedraw::new(a, b).all(|c| led_addr_oddr(c.into()).is_ok())
+        };
+        let (a, b) = leds
+            .iter()
+            .flat_map(|a| leds.iter().map(move |b| (*a, *b)))
+            .find(|(a, b)| a.distance(b) >= 3 && on_matrix(*a, *b))
+            .unwrap();
+        canvas
+            .blended(BlendMode::Add)
+            .polygon(&[a, b], RGB8::new(1, 0, 0));
+        assert!(canvas.iter().all(|c| c.r <= 1));
+        let on_matrix = a.distance(&b) as usize + 1;
+        assert_eq!(canvas.iter().filter(|c| c.r == 1).count(), on_matrix);
+    }
+
+    #[test]
+    fn flood_fill_matches_search() {
+        use rand::{rngs::SmallRng, Rng, SeedableRng};
+        let _lock = crate::matrix::lock_transform();
+        let mut rng = SmallRng::seed_from_u64(3);
+        let colors = [color::BLACK, color::RED, color::WHITE];
+        for _ in 0..50 {
+            // noisy areas make lots of short runs, more than the seed queue holds
+            let mut canvas = [color::BLACK; Matrix::NUM_LEDS];
+            let noise = rng.gen_range(0.0..0.5);
+            for c in canvas.iter_mut() {
+                if rng.gen_bool(noise) {
+                    *c = colors[rng.gen_range(1..3)];
+                }
+            }
+            let (_, start) = crate::matrix::leds_oddr()
+                .nth(rng.gen_range(0..Matrix::NUM_LEDS))
+                .unwrap();
+            let target = canvas.get_oddr(start).unwrap();
+
+            // breadth first search over the leds of the target color
+            let mut expected = canvas;
+            let mut seen = std::collections::HashSet::from([start]);
+            let mut queue = std::collections::VecDeque::from([start]);
+            while let Some(v) = queue.pop_front() {
+                expected.set_oddr(v, color::GREEN);
+                for n in v.neighbors() {
+                    if canvas.get_oddr(n) == Some(target) && seen.insert(n) {
+                        queue.push_back(n);
+                    }
+                }
+            }
+
+            canvas.flood_fill(start.into(), color::GREEN);
+            assert_eq!(canvas, expected);
+        }
+    }
+
+    // expected 16 bit value of every led after drawing white with line_aa
+    fn check_aa(canvas: &[RGB16; Matrix::NUM_LEDS], weights: &[((i32, i32), f32)]) {
+        for (i, c) in crate::matrix::leds_cube() {
+            let hex::Axial { q, r } = c.into();
+            let w = weights
+                .iter()
+                .filter(|(cell, _)| *cell == (q, r))
+                .map(|(_, w)| w)
+                .sum::<f32>();
+            let expected = (w * 65535.0) as i32;
+            assert!(
+                (canvas[i].r as i32 - expected).abs() <= 2,
+                "{q} {r}: {canvas:?}"
+            );
+            assert_eq!(canvas[i].r, canvas[i].b);
+        }
+    }
+
+    #[test]
+    fn line_aa_covers_two_cells() {
+        let _lock = crate::matrix::lock_transform();
+        let h = hex::SQRT3_2;
+        let mut canvas = [RGB16::default(); Matrix::NUM_LEDS];
+        // through the cell centers of row 0
+        canvas.line_aa((-2.0, 0.0), (3.0, 0.0), color::WHITE);
+        let row: std::vec::Vec<_> = (-2..=3).map(|q| ((q, 0), 1.0)).collect();
+        check_aa(&canvas, &row);
+
+        // halfway between rows 0 and 1
+        canvas.clear();
+        canvas.line_aa((-2.0, h / 2.0), (3.0, h / 2.0), color::WHITE);
+        let rows: std::vec::Vec<_> = (-2..=3)
+            .flat_map(|q| [((q, 0), 0.5), ((q, 1), 0.5)])
+            .collect();
+        check_aa(&canvas, &rows);
+
+        // vertical: centered on the cells of even rows, between two cells on odd rows
+        canvas.clear();
+        canvas.line_aa((0.0, 4.0 * h), (0.0, -2.0 * h), color::WHITE);
+        let column: std::vec::Vec<_> = (-2..=4)
+            .flat_map(|r: i32| {
+                let q = -r.div_euclid(2);
+                if r % 2 == 0 {
+                    [((q, r), 1.0), ((q - 1, r), 0.0)]
+                } else {
+                    [((q - 1, r), 0.5), ((q, r), 0.5)]
+                }
+            })
+            .collect();
+        check_aa(&canvas, &column);
+    }
+
+    #[test]
+    fn blend_keeps_16_bit() {
+        let _lock = crate::matrix::lock_transform();
+        let v = crate::matrix::LED_COORDS[0];
+        let mut canvas = [RGB16::default(); Matrix::NUM_LEDS];
+        // far below one 8 bit step
+        canvas.blend_oddr_weighted(v, color::WHITE, 100, BlendMode::Add);
+        canvas.blend_oddr_weighted(v, color::WHITE, 100, BlendMode::Add);
+        let addr = led_addr_oddr(v).unwrap();
+        assert_eq!(canvas[addr], RGB16::new(200, 200, 200));
+        canvas
+            .blended(BlendMode::Add)
+            .set_oddr(v, RGB8::new(1, 0, 0));
+        assert_eq!(canvas[addr], RGB16::new(200 + 257, 200, 200));
+    }
 }
@@ -1,4 +1,9 @@
-use smart_leds::RGB8;
+use smart_leds::{RGB, RGB8};
+
+use crate::oklab;
+
+// Higher precision render buffer (see App::tick16), 0..=65535 per channel
+pub type RGB16 = RGB<u16>;
 
 pub struct Rainbow {
     pos: u8,
@@ -69,6 +74,94 @@ pub const WHITE: RGB8 = RGB8 {
     b: 255,
 };
 
+// scale all channels by s / 255
+pub fn scale(c: RGB8, s: u8) -> RGB8 {
+    RGB8::new(
+        (c.r as u16 * s as u16 / 255) as u8,
+        (c.g as u16 * s as u16 / 255) as u8,
+        (c.b as u16 * s as u16 / 255) as u8,
+    )
+}
+
+// RGB8 -> RGB16, 255 maps to 65535
+pub fn to_rgb16(c: RGB8) -> RGB16 {
+    RGB16::new(c.r as u16 * 257, c.g as u16 * 257, c.b as u16 * 257)
+}
+
+// rounded, to_rgb8(to_rgb16(c)) == c
+pub fn to_rgb8(c: RGB16) -> RGB8 {
+    let narrow = |v: u16| ((v as u32 + 128) / 257) as u8;
+    RGB8::new(narrow(c.r), narrow(c.g), narrow(c.b))
+}
+
+// scale all channels by (s + 1) / 256, like smart_leds::brightness
+pub fn scale16(c: RGB16, s: u8) -> RGB16 {
+    let f = |v: u16| ((v as u32 * (s as u32 + 1)) >> 8) as u16;
+    RGB16::new(f(c.r), f(c.g), f(c.b))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    Replace,
+    // per channel saturating add
+    Add,
+    Multiply,
+    Screen,
+    // src over dst with 8-bit opacity
+    Alpha(u8),
+    Max,
+    // like Alpha, but mixed in OKLab (see oklab::lerp)
+    OkLab(u8),
+}
+
+pub fn blend(dst: RGB8, src: RGB8, mode: BlendMode) -> RGB8 {
+    match mode {
+        BlendMode::Replace => src,
+        BlendMode::Add => per_channel(dst, src, |d, s| (d + s).min(255)),
+        BlendMode::Multiply => per_channel(dst, src, |d, s| d * s / 255),
+        BlendMode::Screen => per_channel(dst, src, |d, s| 255 - (255 - d) * (255 - s) / 255),
+        BlendMode::Alpha(a) => {
+            let a = a as u16;
+            per_channel(dst, src, |d, s| (s * a + d * (255 - a)) / 255)
+        }
+        BlendMode::Max => per_channel(dst, src, |d, s| d.max(s)),
+        // mixes all channels at once
+        BlendMode::OkLab(a) => oklab::lerp(dst, src, a),
+    }
+}
+
+// f(dst, src) for each channel, widened to 16 bit, results must be within 0..=255
+fn per_channel(dst: RGB8, src: RGB8, f: impl Fn(u16, u16) -> u16) -> RGB8 {
+    let c = |d: u8, s: u8| f(d as u16, s as u16) as u8;
+    RGB8::new(c(dst.r, src.r), c(dst.g, src.g), c(dst.b, src.b))
+}
+
+// blend for 16 bit canvases, the opacity of Alpha and OkLab stays 8 bit
+pub fn blend16(dst: RGB16, src: RGB16, mode: BlendMode) -> RGB16 {
+    match mode {
+        BlendMode::Replace => src,
+        BlendMode::Add => per_channel16(dst, src, |d, s| (d + s).min(65535)),
+        BlendMode::Multiply => per_channel16(dst, src, |d, s| d * s / 65535),
+        BlendMode::Screen => {
+            per_channel16(dst, src, |d, s| 65535 - (65535 - d) * (65535 - s) / 65535)
+        }
+        BlendMode::Alpha(a) => {
+            let a = a as u32;
+            per_channel16(dst, src, |d, s| (s * a + d * (255 - a)) / 255)
+        }
+        BlendMode::Max => per_channel16(dst, src, |d, s| d.max(s)),
+        // OKLab is computed from 8 bit, only the result keeps 16 bit
+        BlendMode::OkLab(a) => oklab::Lab::from(to_rgb8(dst))
+            .lerp(&oklab::Lab::from(to_rgb8(src)), a)
+            .into(),
+    }
+}
+
+fn per_channel16(dst: RGB16, src: RGB16, f: impl Fn(u32, u32) -> u32) -> RGB16 {
+    let c = |d: u16, s: u16| f(d as u32, s as u32) as u16;
+    RGB16::new(c(dst.r, src.r), c(dst.g, src.g), c(dst.b, src.b))
+}
+
 // simple hue/value color representation
 #[derive(Clone, Copy)]
 pub struct HV8 {
@@ -82,30 +175,156 @@ impl HV8 {
     }
 }
 
-const GAMMA8: [u16; 256] = [
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1,
-    1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 5, 5, 5,
-    5, 6, 6, 6, 6, 7, 7, 7, 7, 8, 8, 8, 9, 9, 9, 10, 10, 10, 11, 11, 11, 12, 12, 13, 13, 13, 14,
-    14, 15, 15, 16, 16, 17, 17, 18, 18, 19, 19, 20, 20, 21, 21, 22, 22, 23, 24, 24, 25, 25, 26, 27,
-    27, 28, 29, 29, 30, 31, 32, 32, 33, 34, 35, 35, 36, 37, 38, 39, 39, 40, 41, 42, 43, 44, 45, 46,
-    47, 48, 49, 50, 50, 51, 52, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 66, 67, 68, 69, 70, 72,
-    73, 74, 75, 77, 78, 79, 81, 82, 83, 85, 86, 87, 89, 90, 92, 93, 95, 96, 98, 99, 101, 102, 104,
-    105, 107, 109, 110, 112, 114, 115, 117, 119, 120, 122, 124, 126, 127, 129, 131, 133, 135, 137,
-    138, 140, 142, 144, 146, 148, 150, 152, 154, 156, 158, 160, 162, 164, 167, 169, 171, 173, 175,
-    177, 180, 182, 184, 186, 189, 191, 193, 196, 198, 200, 203, 205, 208, 210, 213, 215, 218, 220,
-    223, 225, 228, 231, 233, 236, 239, 241, 244, 247, 249, 252, 255,
-];
+// Hue / saturation / value. Hue uses about the positions of wheel (0 red, 85 green, 171
+// blue, 256 is a full turn), but the conversion to RGB8 ramps the channels in six sectors,
+// so fully saturated colors keep their brightest channel at v all the way around. All math
+// is 8 / 16 bit fixed point (no float, no division towards RGB8).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HSV8 {
+    pub h: u8,
+    pub s: u8,
+    pub v: u8,
+}
+
+impl HSV8 {
+    pub const fn new(h: u8, s: u8, v: u8) -> HSV8 {
+        HSV8 { h, s, v }
+    }
+
+    // t = 0 is self, 255 is other. Hue goes the shorter way around the wheel, and is taken
+    // from the other side if one of them has no hue (black or gray).
+    pub fn lerp(&self, other: &HSV8, t: u8) -> HSV8 {
+        let no_hue = |c: &HSV8| c.v == 0 || c.s == 0;
+        let (from, to) = match (no_hue(self), no_hue(other)) {
+            (true, false) => (other.h, other.h),
+            (false, true) => (self.h, self.h),
+            _ => (self.h, other.h),
+        };
+        let dh = to.wrapping_sub(from) as i8 as i32;
+        HSV8 {
+            h: from.wrapping_add((dh * t as i32 / 255) as u8),
+            s: lerp8(self.s, other.s, t),
+            v: lerp8(self.v, other.v, t),
+        }
+    }
+}
+
+impl From<HV8> for HSV8 {
+    fn from(hv: HV8) -> Self {
+        HSV8::new(hv.h, 255, hv.v)
+    }
+}
+
+impl From<HSV8> for RGB8 {
+    fn from(c: HSV8) -> Self {
+        // hue in sixths of a turn with 256 steps each
+        let h6 = c.h as u16 * 6;
+        let (sector, f) = (h6 >> 8, h6 as u8);
+        let v = c.v;
+        let p = scale8(v, 255 - c.s);
+        let q = scale8(v, 255 - scale8(c.s, f));
+        let t = scale8(v, 255 - scale8(c.s, 255 - f));
+        match sector {
+            0 => RGB8::new(v, t, p),
+            1 => RGB8::new(q, v, p),
+            2 => RGB8::new(p, v, t),
+            3 => RGB8::new(p, q, v),
+            4 => RGB8::new(t, p, v),
+            _ => RGB8::new(v, p, q),
+        }
+    }
+}
+
+impl From<&HSV8> for RGB8 {
+    fn from(c: &HSV8) -> Self {
+        (*c).into()
+    }
+}
+
+impl From<RGB8> for HSV8 {
+    fn from(c: RGB8) -> Self {
+        let (r, g, b) = (c.r as i32, c.g as i32, c.b as i32);
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+        if delta == 0 {
+            return HSV8::new(0, 0, max as u8);
+        }
+        let h6 = if max == r {
+            (g - b) * 256 / delta
+        } else if max == g {
+            512 + (b - r) * 256 / delta
+        } else {
+            1024 + (r - g) * 256 / delta
+        };
+        HSV8 {
+            // rounded, 256 wraps around to 0
+            h: ((h6.rem_euclid(6 * 256) + 3) / 6) as u8,
+            s: (delta * 255 / max) as u8,
+            v: max as u8,
+        }
+    }
+}
+
+// a * b / 255 without division, exact for b = 0 and b = 255
+fn scale8(a: u8, b: u8) -> u8 {
+    ((a as u16 * (b as u16 + 1)) >> 8) as u8
+}
+
+// t = 0 is a, 255 is b
+pub const fn lerp8(a: u8, b: u8, t: u8) -> u8 {
+    (a as i32 + (b as i32 - a as i32) * t as i32 / 255) as u8
+}
 
 impl From<&HV8> for RGB8 {
     fn from(val: &HV8) -> Self {
-        let RGB8 { r, g, b } = wheel(val.h);
-        let v = val.v as usize;
-        RGB8::new(
-            (r as u16 * GAMMA8[v] / 255u16) as u8,
-            (g as u16 * GAMMA8[v] / 255u16) as u8,
-            (b as u16 * GAMMA8[v] / 255u16) as u8,
-        )
+        // linear, gamma is applied by the output stage
+        scale(wheel(val.h), val.v)
         // let rgb = color::wheel(self.h);
         // brightness(iter, brightness)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    #[test]
+    fn hsv_roundtrip() {
+        for h in 0..=255 {
+            let c = HSV8::new(h, 255, 255);
+            let back = HSV8::from(RGB8::from(c));
+            assert!((back.h.wrapping_sub(h) as i8).abs() <= 1, "{c:?} {back:?}");
+            assert_eq!((back.s, back.v), (255, 255));
+        }
+        let mut rng = SmallRng::seed_from_u64(1);
+        // hue is quantized to 256 steps, which costs a few rgb steps at full value
+        for _ in 0..10000 {
+            let c = RGB8::new(rng.gen(), rng.gen(), rng.gen());
+            let back = RGB8::from(HSV8::from(c));
+            let err = |a: u8, b: u8| (a as i32 - b as i32).abs();
+            assert!(err(c.r, back.r).max(err(c.g, back.g)).max(err(c.b, back.b)) <= 4);
+        }
+        for v in [0, 1, 128, 255] {
+            let gray = RGB8::new(v, v, v);
+            assert_eq!(RGB8::from(HSV8::from(gray)), gray);
+        }
+    }
+
+    #[test]
+    fn hsv_lerp_hue_wraps() {
+        let (a, b) = (HSV8::new(250, 255, 255), HSV8::new(10, 255, 255));
+        assert_eq!(a.lerp(&b, 0), a);
+        assert_eq!(a.lerp(&b, 255), b);
+        assert_eq!(a.lerp(&b, 128).h, 2);
+        assert_eq!(b.lerp(&a, 128).h, 2);
+        // never takes the long way through green and blue
+        for t in 0..=255 {
+            let h = a.lerp(&b, t).h;
+            assert!(h >= 250 || h <= 10, "{t} {h}");
+        }
+        // black has no hue, only the value fades
+        let black = HSV8::new(128, 255, 0);
+        assert_eq!(black.lerp(&b, 128), HSV8::new(10, 255, 128));
+    }
+}
@@ -0,0 +1,106 @@
+use crate::color::{self, BlendMode, RGB16};
+use crate::prelude::*;
+
+// Layers hold 16 bit colors, so the composited frame keeps the precision of App::tick16
+// all the way to the output stage.
+pub struct Layer {
+    pub data: [RGB16; Matrix::NUM_LEDS],
+    // how the layer is put onto the ones below
+    pub mode: BlendMode,
+    // mixes the blend result with what is below, 255 is the plain blend result
+    pub opacity: u8,
+    pub visible: bool,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Layer {
+            data: [RGB16::default(); Matrix::NUM_LEDS],
+            mode: BlendMode::Replace,
+            opacity: 255,
+            visible: true,
+        }
+    }
+}
+
+impl Layer {
+    pub fn new(mode: BlendMode) -> Self {
+        Layer {
+            mode,
+            ..Default::default()
+        }
+    }
+}
+
+// Stack of L framebuffers, flattened bottom (index 0) to top into the final led frame.
+pub struct Compositor<const L: usize> {
+    pub layers: [Layer; L],
+}
+
+impl<const L: usize> Default for Compositor<L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const L: usize> Compositor<L> {
+    pub fn new() -> Self {
+        Compositor {
+            layers: core::array::from_fn(|_| Layer::default()),
+        }
+    }
+
+    // let each app render into its own layer (apps[i] -> layers[i])
+    pub fn tick(&mut self, apps: [&mut dyn App; L], env: &Env) {
+        for (layer, app) in self.layers.iter_mut().zip(apps) {
+            app.tick16(&mut layer.data, env);
+        }
+    }
+
+    pub fn flatten(&self, out: &mut [RGB16; Matrix::NUM_LEDS]) {
+        out.fill(RGB16::default());
+        for layer in self.layers.iter().filter(|l| l.visible) {
+            for (dst, src) in out.iter_mut().zip(layer.data.iter()) {
+                let c = color::blend16(*dst, *src, layer.mode);
+                *dst = match layer.opacity {
+                    255 => c,
+                    a => color::blend16(*dst, c, BlendMode::Alpha(a)),
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flatten_modes_and_opacity() {
+        let mut layers = Compositor::<3>::new();
+        let (a, b, c) = (0, 1, 2);
+        layers.layers[0].data[a] = RGB16::new(40000, 0, 1000);
+        layers.layers[0].data[b] = RGB16::new(1000, 2000, 3000);
+        layers.layers[1] = Layer::new(BlendMode::Add);
+        layers.layers[1].opacity = 128;
+        layers.layers[1].data[a] = RGB16::new(40000, 20000, 0);
+        layers.layers[1].data[c] = RGB16::new(510, 0, 65535);
+        layers.layers[2] = Layer::new(BlendMode::Max);
+        layers.layers[2].data[b] = RGB16::new(0, 65535, 0);
+        let mut out = [RGB16::new(1, 1, 1); Matrix::NUM_LEDS];
+        layers.flatten(&mut out);
+        // Add saturates, then half of it over the base
+        let mix = |d: u32, s: u32| ((s * 128 + d * 127) / 255) as u16;
+        assert_eq!(out[a], RGB16::new(mix(40000, 65535), mix(0, 20000), 1000));
+        assert_eq!(out[b], RGB16::new(1000, 65535, 3000));
+        assert_eq!(out[c], RGB16::new(mix(0, 510), 0, mix(0, 65535)));
+        // untouched leds are cleared
+        assert!(out[3..].iter().all(|c| *c == RGB16::default()));
+
+        layers.layers[2].visible = false;
+        layers.layers[1].opacity = 255;
+        layers.flatten(&mut out);
+        assert_eq!(out[a], RGB16::new(65535, 20000, 1000));
+        assert_eq!(out[b], RGB16::new(1000, 2000, 3000));
+    }
+}
@@ -5,10 +5,10 @@ use crate::prelude::*;
 pub fn kitt<WS: SmartLedsWrite<Color = RGB8, Error = Error>>(
     ws: &mut WS,
     colors: &mut dyn Iterator<Item = RGB8>,
-    data: &mut [RGB8; NUM_LEDS],
+    data: &mut [RGB8; Matrix::NUM_LEDS],
 ) {
-    let up = 0..MATRIX_WIDTH;
-    let down = (0..MATRIX_WIDTH).rev();
+    let up = 0..Matrix::WIDTH;
+    let down = (0..Matrix::WIDTH).rev();
     let pause = core::iter::repeat(20).take(100);
     let pause_short = core::iter::repeat(20).take(20);
     let seq = up.chain(pause_short).chain(down).chain(pause);
@@ -16,10 +16,10 @@ pub fn kitt<WS: SmartLedsWrite<Color = RGB8, Error = Error>>(
         data.iter_mut().for_each(|v| {
             *v = brightness(core::iter::once(*v), 210).next().unwrap();
         });
-        if cur < MATRIX_WIDTH {
+        if cur < Matrix::WIDTH {
             let c = colors.next().unwrap();
 
-            for y in 0..MATRIX_HEIGHT {
+            for y in 0..Matrix::HEIGHT {
                 let _ = set_matrix(cur, y, c, data);
             }
         }
@@ -0,0 +1,202 @@
+use smart_leds::RGB8;
+
+use crate::{
+    math::Vec2,
+    matrix::{self, Matrix, Shape},
+    palette::{Level, Palette},
+};
+
+const SIZE: usize = Matrix::WIDTH * Matrix::HEIGHT;
+
+// what reading a cell outside of the matrix yields
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bounds {
+    // value of the nearest cell on the border of the matrix rectangle. Unless the layout is
+    // a rectangle that is a cell without led, which still takes part in the simulation.
+    Clamp,
+    // continue on the opposite side. With an odd number of rows the row parity flips when
+    // wrapping vertically, so neighbors across the top / bottom edge are off by half a cell.
+    Wrap,
+    // T::default()
+    Zero,
+}
+
+// One value per matrix cell, addressed by matrix coordinates (like set_matrix, not
+// set_matrix_oddr). Cells without led take part in the simulation, they are just not
+// displayed.
+#[derive(Clone)]
+pub struct HexField<T> {
+    data: [T; SIZE],
+    back: [T; SIZE],
+    pub bounds: Bounds,
+}
+
+impl<T: Copy + Default> HexField<T> {
+    pub fn new(bounds: Bounds) -> Self {
+        HexField {
+            data: [T::default(); SIZE],
+            back: [T::default(); SIZE],
+            bounds,
+        }
+    }
+
+    fn index(&self, v: Vec2) -> Option<usize> {
+        let (w, h) = (Matrix::WIDTH as i32, Matrix::HEIGHT as i32);
+        let (x, y) = match self.bounds {
+            Bounds::Clamp => (v.x.clamp(0, w - 1), v.y.clamp(0, h - 1)),
+            Bounds::Wrap => (v.x.rem_euclid(w), v.y.rem_euclid(h)),
+            Bounds::Zero => {
+                if v.x < 0 || v.x >= w || v.y < 0 || v.y >= h {
+                    return None;
+                }
+                (v.x, v.y)
+            }
+        };
+        Some(y as usize * Matrix::WIDTH + x as usize)
+    }
+
+    pub fn get(&self, v: Vec2) -> T {
+        self.index(v).map(|i| self.data[i]).unwrap_or_default()
+    }
+
+    // writes outside of the matrix are ignored, regardless of bounds
+    pub fn set(&mut self, v: Vec2, value: T) {
+        if v.x >= 0 && (v.x as usize) < Matrix::WIDTH && v.y >= 0 && (v.y as usize) < Matrix::HEIGHT
+        {
+            self.data[v.y as usize * Matrix::WIDTH + v.x as usize] = value;
+        }
+    }
+
+    pub fn fill(&mut self, value: T) {
+        self.data.fill(value);
+    }
+
+    // values of the six neighbors of v, in CUBE_DIRECTIONS order
+    pub fn neighbors(&self, v: Vec2) -> [T; 6] {
+        Matrix::adjacent(v).map(|n| self.get(n))
+    }
+
+    // Compute the next generation: f is called for every cell with the current field and
+    // the cell position, its results only become visible after all cells are done.
+    pub fn step(&mut self, mut f: impl FnMut(&Self, Vec2) -> T) {
+        for y in 0..Matrix::HEIGHT {
+            for x in 0..Matrix::WIDTH {
+                let value = f(self, Vec2::new(x as i32, y as i32));
+                self.back[y * Matrix::WIDTH + x] = value;
+            }
+        }
+        core::mem::swap(&mut self.data, &mut self.back);
+    }
+
+    // Update all cells in place, row by row from the top left: f sees the new values of the
+    // cells already visited (the row above and the cells to the left) and the old ones of
+    // the rest.
+    pub fn update(&mut self, mut f: impl FnMut(&Self, Vec2) -> T) {
+        for y in 0..Matrix::HEIGHT {
+            for x in 0..Matrix::WIDTH {
+                let value = f(self, Vec2::new(x as i32, y as i32));
+                self.data[y * Matrix::WIDTH + x] = value;
+            }
+        }
+    }
+
+    // map the cells with leds through palette
+    pub fn to_leds(&self, leds: &mut [RGB8; Matrix::NUM_LEDS], palette: impl Fn(T) -> RGB8) {
+        for (i, x, y) in matrix::leds_xy() {
+            leds[i] = palette(self.data[y * Matrix::WIDTH + x]);
+        }
+    }
+
+    // map the cells with leds through a color palette
+    pub fn to_leds_palette<const N: usize>(
+        &self,
+        leds: &mut [RGB8; Matrix::NUM_LEDS],
+        palette: &Palette<N>,
+    ) where
+        T: Level,
+    {
+        self.to_leds(leds, |v| palette.get(v.level()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const W: i32 = Matrix::WIDTH as i32;
+    const H: i32 = Matrix::HEIGHT as i32;
+
+    // every cell holds a distinct non zero value
+    fn numbered(bounds: Bounds) -> HexField<i32> {
+        let mut field = HexField::new(bounds);
+        field.step(|_, v| v.y * W + v.x + 1);
+        field
+    }
+
+    fn value(x: i32, y: i32) -> i32 {
+        y * W + x + 1
+    }
+
+    #[test]
+    fn bounds() {
+        let v = Vec2::new;
+        let field = numbered(Bounds::Clamp);
+        assert_eq!(field.get(v(-1, 0)), value(0, 0));
+        assert_eq!(field.get(v(-3, -2)), value(0, 0));
+        assert_eq!(field.get(v(2, H + 5)), value(2, H - 1));
+        assert_eq!(field.get(v(W, H - 1)), value(W - 1, H - 1));
+
+        let field = numbered(Bounds::Wrap);
+        assert_eq!(field.get(v(-1, 0)), value(W - 1, 0));
+        assert_eq!(field.get(v(0, -1)), value(0, H - 1));
+        assert_eq!(field.get(v(W, H)), value(0, 0));
+        assert_eq!(field.get(v(2 * W + 1, -H - 2)), value(1, H - 2));
+
+        let field = numbered(Bounds::Zero);
+        assert_eq!(field.get(v(-1, 0)), 0);
+        assert_eq!(field.get(v(0, -1)), 0);
+        assert_eq!(field.get(v(W, H - 1)), 0);
+        assert_eq!(field.get(v(W - 1, H - 1)), value(W - 1, H - 1));
+
+        // neighbors read through the same bounds
+        for bounds in [Bounds::Clamp, Bounds::Wrap, Bounds::Zero] {
+            let field = numbered(bounds);
+            for c in [v(0, 0), v(W - 1, 0), v(0, H - 1), v(W - 1, H - 1)] {
+                let expected = Matrix::adjacent(c).map(|n| field.get(n));
+                assert_eq!(field.neighbors(c), expected);
+            }
+        }
+        // west neighbor of the top left corner
+        assert_eq!(numbered(Bounds::Clamp).neighbors(v(0, 0))[3], value(0, 0));
+        assert_eq!(
+            numbered(Bounds::Wrap).neighbors(v(0, 0))[3],
+            value(W - 1, 0)
+        );
+        assert_eq!(numbered(Bounds::Zero).neighbors(v(0, 0))[3], 0);
+    }
+
+    #[test]
+    fn step_and_update() {
+        // one more than the west neighbor
+        let rule = |f: &HexField<i32>, v: Vec2| f.neighbors(v)[3] + 1;
+
+        // step only sees the previous generation
+        let mut field = HexField::new(Bounds::Zero);
+        field.step(rule);
+        assert!(field.data.iter().all(|v| *v == 1));
+        field.step(rule);
+        for y in 0..H {
+            assert_eq!(field.get(Vec2::new(0, y)), 1);
+            assert_eq!(field.get(Vec2::new(W - 1, y)), 2);
+        }
+
+        // update sees the cells to the left already updated
+        let mut field = HexField::new(Bounds::Zero);
+        field.update(rule);
+        for y in 0..H {
+            for x in 0..W {
+                assert_eq!(field.get(Vec2::new(x, y)), x + 1);
+            }
+        }
+    }
+}
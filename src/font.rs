@@ -0,0 +1,312 @@
+use smart_leds::RGB8;
+
+use crate::matrix::{self, Matrix, Shape};
+
+// Fixed width bitmap font. Glyphs are stored column by column, one byte per column with
+// the top row in bit 0, for the ascii range first..=last.
+pub struct Font {
+    pub width: usize,
+    pub height: usize,
+    first: u8,
+    last: u8,
+    // use uppercase glyphs for lowercase letters (font has no lowercase)
+    upper_only: bool,
+    glyphs: &'static [u8],
+}
+
+impl Font {
+    // horizontal distance between glyphs, including one column of spacing
+    pub fn advance(&self) -> usize {
+        self.width + 1
+    }
+    pub fn text_width(&self, text: &str) -> usize {
+        text.chars().count() * self.advance()
+    }
+    fn glyph(&self, c: char) -> &'static [u8] {
+        let c = if self.upper_only {
+            c.to_ascii_uppercase()
+        } else {
+            c
+        };
+        let i = if (self.first as char..=self.last as char).contains(&c) {
+            c as usize - self.first as usize
+        } else {
+            '?' as usize - self.first as usize
+        };
+        &self.glyphs[i * self.width..(i + 1) * self.width]
+    }
+    // pixel (x, y) of the rendered text line, anything outside of the text is unset
+    pub fn pixel(&self, text: &str, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || y as usize >= self.height {
+            return false;
+        }
+        let (i, col) = (x as usize / self.advance(), x as usize % self.advance());
+        if col >= self.width {
+            return false;
+        }
+        match text.chars().nth(i) {
+            Some(c) => self.glyph(c)[col] & (1 << y) != 0,
+            None => false,
+        }
+    }
+}
+
+// Render a line of text with its top left corner at (x, y) in matrix coordinates. x is given
+// in half cells: odd rows of the odd-r layout sit half a cell to the right, so every led
+// samples the text at its actual horizontal position instead of its column index. This keeps
+// glyphs from shearing and allows scrolling in half cell steps.
+pub fn draw_text(
+    data: &mut [RGB8; Matrix::NUM_LEDS],
+    font: &Font,
+    text: &str,
+    x_half: i32,
+    y: i32,
+    color: RGB8,
+) {
+    let (w, h) = (Matrix::WIDTH as i32, Matrix::HEIGHT as i32);
+    for (i, c) in text.chars().enumerate() {
+        let left = (i * font.advance()) as i32;
+        // glyphs left / right of the matrix (x_half / 2 is where the text starts)
+        if 2 * (left + font.width as i32) + x_half < 0 {
+            continue;
+        }
+        if 2 * left + x_half > 2 * w {
+            break;
+        }
+        for (col, bits) in font.glyph(c).iter().enumerate() {
+            let tx = left + col as i32;
+            for ty in (0..font.height).filter(|ty| bits & (1 << ty) != 0) {
+                let my = y + ty as i32;
+                // the one cell of the row whose position (in half cells) is 2 tx or 2 tx + 1
+                let mx = (2 * tx + x_half - (my & 1) + 1).div_euclid(2);
+                if (0..w).contains(&mx) && (0..h).contains(&my) {
+                    // dead cells outside of the rounded outline are rejected by set_matrix
+                    let _ = matrix::set_matrix(mx as usize, my as usize, color, data);
+                }
+            }
+        }
+    }
+}
+
+pub const FONT_3X5: Font = Font {
+    width: 3,
+    height: 5,
+    first: b' ',
+    last: b'^',
+    upper_only: true,
+    glyphs: &GLYPHS_3X5,
+};
+
+pub const FONT_5X7: Font = Font {
+    width: 5,
+    height: 7,
+    first: b' ',
+    last: b'~',
+    upper_only: false,
+    glyphs: &GLYPHS_5X7,
+};
+
+#[rustfmt::skip]
+const GLYPHS_3X5: [u8; 63 * 3] = [
+    0x00, 0x00, 0x00, // ' '
+    0x00, 0x17, 0x00, // '!'
+    0x03, 0x00, 0x03, // '"'
+    0x1f, 0x0a, 0x1f, // '#'
+    0x12, 0x1f, 0x09, // '$'
+    0x19, 0x04, 0x13, // '%'
+    0x0a, 0x15, 0x1a, // '&'
+    0x00, 0x03, 0x00, // '''
+    0x0e, 0x11, 0x00, // '('
+    0x00, 0x11, 0x0e, // ')'
+    0x05, 0x02, 0x05, // '*'
+    0x04, 0x0e, 0x04, // '+'
+    0x10, 0x08, 0x00, // ','
+    0x04, 0x04, 0x04, // '-'
+    0x00, 0x10, 0x00, // '.'
+    0x18, 0x04, 0x03, // '/'
+    0x1f, 0x11, 0x1f, // '0'
+    0x12, 0x1f, 0x10, // '1'
+    0x19, 0x15, 0x12, // '2'
+    0x11, 0x15, 0x0a, // '3'
+    0x07, 0x04, 0x1f, // '4'
+    0x17, 0x15, 0x09, // '5'
+    0x1e, 0x15, 0x1d, // '6'
+    0x01, 0x1d, 0x03, // '7'
+    0x1f, 0x15, 0x1f, // '8'
+    0x17, 0x15, 0x0f, // '9'
+    0x00, 0x0a, 0x00, // ':'
+    0x10, 0x0a, 0x00, // ';'
+    0x04, 0x0a, 0x11, // '<'
+    0x0a, 0x0a, 0x0a, // '='
+    0x11, 0x0a, 0x04, // '>'
+    0x01, 0x15, 0x02, // '?'
+    0x0f, 0x11, 0x17, // '@'
+    0x1e, 0x05, 0x1e, // 'A'
+    0x1f, 0x15, 0x0a, // 'B'
+    0x0e, 0x11, 0x11, // 'C'
+    0x1f, 0x11, 0x0e, // 'D'
+    0x1f, 0x15, 0x11, // 'E'
+    0x1f, 0x05, 0x01, // 'F'
+    0x0e, 0x11, 0x1d, // 'G'
+    0x1f, 0x04, 0x1f, // 'H'
+    0x11, 0x1f, 0x11, // 'I'
+    0x08, 0x10, 0x0f, // 'J'
+    0x1f, 0x04, 0x1b, // 'K'
+    0x1f, 0x10, 0x10, // 'L'
+    0x1f, 0x06, 0x1f, // 'M'
+    0x1f, 0x01, 0x1e, // 'N'
+    0x0e, 0x11, 0x0e, // 'O'
+    0x1f, 0x05, 0x02, // 'P'
+    0x0e, 0x19, 0x16, // 'Q'
+    0x1f, 0x05, 0x1a, // 'R'
+    0x12, 0x15, 0x09, // 'S'
+    0x01, 0x1f, 0x01, // 'T'
+    0x1f, 0x10, 0x1f, // 'U'
+    0x0f, 0x10, 0x0f, // 'V'
+    0x1f, 0x0c, 0x1f, // 'W'
+    0x1b, 0x04, 0x1b, // 'X'
+    0x03, 0x1c, 0x03, // 'Y'
+    0x19, 0x15, 0x13, // 'Z'
+    0x1f, 0x11, 0x00, // '['
+    0x03, 0x04, 0x18, // '\'
+    0x00, 0x11, 0x1f, // ']'
+    0x02, 0x01, 0x02, // '^'
+];
+
+#[rustfmt::skip]
+const GLYPHS_5X7: [u8; 95 * 5] = [
+    0x00, 0x00, 0x00, 0x00, 0x00, // ' '
+    0x00, 0x00, 0x5f, 0x00, 0x00, // '!'
+    0x00, 0x07, 0x00, 0x07, 0x00, // '"'
+    0x14, 0x7f, 0x14, 0x7f, 0x14, // '#'
+    0x24, 0x2a, 0x7f, 0x2a, 0x12, // '$'
+    0x23, 0x13, 0x08, 0x64, 0x62, // '%'
+    0x36, 0x49, 0x55, 0x22, 0x50, // '&'
+    0x00, 0x05, 0x03, 0x00, 0x00, // '''
+    0x00, 0x1c, 0x22, 0x41, 0x00, // '('
+    0x00, 0x41, 0x22, 0x1c, 0x00, // ')'
+    0x08, 0x2a, 0x1c, 0x2a, 0x08, // '*'
+    0x08, 0x08, 0x3e, 0x08, 0x08, // '+'
+    0x00, 0x50, 0x30, 0x00, 0x00, // ','
+    0x08, 0x08, 0x08, 0x08, 0x08, // '-'
+    0x00, 0x60, 0x60, 0x00, 0x00, // '.'
+    0x20, 0x10, 0x08, 0x04, 0x02, // '/'
+    0x3e, 0x51, 0x49, 0x45, 0x3e, // '0'
+    0x00, 0x42, 0x7f, 0x40, 0x00, // '1'
+    0x42, 0x61, 0x51, 0x49, 0x46, // '2'
+    0x21, 0x41, 0x45, 0x4b, 0x31, // '3'
+    0x18, 0x14, 0x12, 0x7f, 0x10, // '4'
+    0x27, 0x45, 0x45, 0x45, 0x39, // '5'
+    0x3c, 0x4a, 0x49, 0x49, 0x30, // '6'
+    0x01, 0x71, 0x09, 0x05, 0x03, // '7'
+    0x36, 0x49, 0x49, 0x49, 0x36, // '8'
+    0x06, 0x49, 0x49, 0x29, 0x1e, // '9'
+    0x00, 0x36, 0x36, 0x00, 0x00, // ':'
+    0x00, 0x56, 0x36, 0x00, 0x00, // ';'
+    0x08, 0x14, 0x22, 0x41, 0x00, // '<'
+    0x14, 0x14, 0x14, 0x14, 0x14, // '='
+    0x00, 0x41, 0x22, 0x14, 0x08, // '>'
+    0x02, 0x01, 0x51, 0x09, 0x06, // '?'
+    0x32, 0x49, 0x79, 0x41, 0x3e, // '@'
+    0x7e, 0x11, 0x11, 0x11, 0x7e, // 'A'
+    0x7f, 0x49, 0x49, 0x49, 0x36, // 'B'
+    0x3e, 0x41, 0x41, 0x41, 0x22, // 'C'
+    0x7f, 0x41, 0x41, 0x22, 0x1c, // 'D'
+    0x7f, 0x49, 0x49, 0x49, 0x41, // 'E'
+    0x7f, 0x09, 0x09, 0x01, 0x01, // 'F'
+    0x3e, 0x41, 0x41, 0x51, 0x32, // 'G'
+    0x7f, 0x08, 0x08, 0x08, 0x7f, // 'H'
+    0x00, 0x41, 0x7f, 0x41, 0x00, // 'I'
+    0x20, 0x40, 0x41, 0x3f, 0x01, // 'J'
+    0x7f, 0x08, 0x14, 0x22, 0x41, // 'K'
+    0x7f, 0x40, 0x40, 0x40, 0x40, // 'L'
+    0x7f, 0x02, 0x04, 0x02, 0x7f, // 'M'
+    0x7f, 0x04, 0x08, 0x10, 0x7f, // 'N'
+    0x3e, 0x41, 0x41, 0x41, 0x3e, // 'O'
+    0x7f, 0x09, 0x09, 0x09, 0x06, // 'P'
+    0x3e, 0x41, 0x51, 0x21, 0x5e, // 'Q'
+    0x7f, 0x09, 0x19, 0x29, 0x46, // 'R'
+    0x46, 0x49, 0x49, 0x49, 0x31, // 'S'
+    0x01, 0x01, 0x7f, 0x01, 0x01, // 'T'
+    0x3f, 0x40, 0x40, 0x40, 0x3f, // 'U'
+    0x1f, 0x20, 0x40, 0x20, 0x1f, // 'V'
+    0x7f, 0x20, 0x18, 0x20, 0x7f, // 'W'
+    0x63, 0x14, 0x08, 0x14, 0x63, // 'X'
+    0x03, 0x04, 0x78, 0x04, 0x03, // 'Y'
+    0x61, 0x51, 0x49, 0x45, 0x43, // 'Z'
+    0x00, 0x7f, 0x41, 0x41, 0x00, // '['
+    0x02, 0x04, 0x08, 0x10, 0x20, // '\'
+    0x00, 0x41, 0x41, 0x7f, 0x00, // ']'
+    0x04, 0x02, 0x01, 0x02, 0x04, // '^'
+    0x40, 0x40, 0x40, 0x40, 0x40, // '_'
+    0x00, 0x01, 0x02, 0x04, 0x00, // '`'
+    0x20, 0x54, 0x54, 0x54, 0x78, // 'a'
+    0x7f, 0x48, 0x44, 0x44, 0x38, // 'b'
+    0x38, 0x44, 0x44, 0x44, 0x20, // 'c'
+    0x38, 0x44, 0x44, 0x48, 0x7f, // 'd'
+    0x38, 0x54, 0x54, 0x54, 0x18, // 'e'
+    0x08, 0x7e, 0x09, 0x01, 0x02, // 'f'
+    0x08, 0x54, 0x54, 0x54, 0x3c, // 'g'
+    0x7f, 0x08, 0x04, 0x04, 0x78, // 'h'
+    0x00, 0x44, 0x7d, 0x40, 0x00, // 'i'
+    0x20, 0x40, 0x44, 0x3d, 0x00, // 'j'
+    0x7f, 0x10, 0x28, 0x44, 0x00, // 'k'
+    0x00, 0x41, 0x7f, 0x40, 0x00, // 'l'
+    0x7c, 0x04, 0x18, 0x04, 0x78, // 'm'
+    0x7c, 0x08, 0x04, 0x04, 0x78, // 'n'
+    0x38, 0x44, 0x44, 0x44, 0x38, // 'o'
+    0x7c, 0x14, 0x14, 0x14, 0x08, // 'p'
+    0x08, 0x14, 0x14, 0x18, 0x7c, // 'q'
+    0x7c, 0x08, 0x04, 0x04, 0x08, // 'r'
+    0x48, 0x54, 0x54, 0x54, 0x20, // 's'
+    0x04, 0x3f, 0x44, 0x40, 0x20, // 't'
+    0x3c, 0x40, 0x40, 0x20, 0x7c, // 'u'
+    0x1c, 0x20, 0x40, 0x20, 0x1c, // 'v'
+    0x3c, 0x40, 0x30, 0x40, 0x3c, // 'w'
+    0x44, 0x28, 0x10, 0x28, 0x44, // 'x'
+    0x0c, 0x50, 0x50, 0x50, 0x3c, // 'y'
+    0x44, 0x64, 0x54, 0x4c, 0x44, // 'z'
+    0x00, 0x08, 0x36, 0x41, 0x00, // '{'
+    0x00, 0x00, 0x7f, 0x00, 0x00, // '|'
+    0x00, 0x41, 0x36, 0x08, 0x00, // '}'
+    0x08, 0x04, 0x08, 0x10, 0x08, // '~'
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+
+    // draw_text as it was, sampling font.pixel for every cell
+    fn draw_text_per_pixel(
+        font: &Font,
+        text: &str,
+        x_half: i32,
+        y: i32,
+    ) -> [RGB8; Matrix::NUM_LEDS] {
+        let mut data = [color::BLACK; Matrix::NUM_LEDS];
+        for my in 0..Matrix::HEIGHT {
+            for mx in 0..Matrix::WIDTH {
+                let pos_half = 2 * mx as i32 + (my as i32 & 1) - x_half;
+                if font.pixel(text, pos_half.div_euclid(2), my as i32 - y) {
+                    let _ = matrix::set_matrix(mx, my, color::WHITE, &mut data);
+                }
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn draw_text_matches_per_pixel_sampling() {
+        let _lock = matrix::lock_transform();
+        for font in [&FONT_3X5, &FONT_5X7] {
+            for x_half in -60..44 {
+                for y in [-3, 0, 7, 15] {
+                    let mut data = [color::BLACK; Matrix::NUM_LEDS];
+                    draw_text(&mut data, font, "Hex 42!", x_half, y, color::WHITE);
+                    assert!(data == draw_text_per_pixel(font, "Hex 42!", x_half, y));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use smart_leds::RGB8;
+
+use crate::matrix::{self, Matrix, Shape};
+
+// Frame sequence format written by tools/hexconv, meant to be embedded with include_bytes!.
+// All values little endian:
+//   header: b"HXF1", num_leds: u16, num_frames: u16
+//   frame:  delay_ms: u16, followed by num_leds * (r, g, b) in led order
+// Frames describe the untransformed layout, write_to applies the display transform.
+pub const MAGIC: [u8; 4] = *b"HXF1";
+pub const HEADER_SIZE: usize = 8;
+pub const FRAME_HEADER_SIZE: usize = 2;
+pub const FRAME_SIZE: usize = FRAME_HEADER_SIZE + Matrix::NUM_LEDS * 3;
+
+#[derive(Debug)]
+pub enum Error {
+    BadMagic,
+    // sequence was made for a matrix with a different number of leds
+    WrongLedCount,
+    Truncated,
+}
+
+pub fn header(num_frames: u16) -> [u8; HEADER_SIZE] {
+    let mut header = [0u8; HEADER_SIZE];
+    header[0..4].copy_from_slice(&MAGIC);
+    header[4..6].copy_from_slice(&(Matrix::NUM_LEDS as u16).to_le_bytes());
+    header[6..8].copy_from_slice(&num_frames.to_le_bytes());
+    header
+}
+
+pub struct Frames<'a> {
+    data: &'a [u8],
+    num_frames: usize,
+}
+
+pub struct Frame<'a> {
+    pub delay_ms: u16,
+    rgb: &'a [u8],
+}
+
+impl<'a> Frames<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < HEADER_SIZE {
+            return Err(Error::Truncated);
+        }
+        if data[0..4] != MAGIC {
+            return Err(Error::BadMagic);
+        }
+        if u16::from_le_bytes([data[4], data[5]]) as usize != Matrix::NUM_LEDS {
+            return Err(Error::WrongLedCount);
+        }
+        let num_frames = u16::from_le_bytes([data[6], data[7]]) as usize;
+        if data.len() < HEADER_SIZE + num_frames * FRAME_SIZE {
+            return Err(Error::Truncated);
+        }
+        Ok(Frames {
+            data: &data[HEADER_SIZE..],
+            num_frames,
+        })
+    }
+    pub fn len(&self) -> usize {
+        self.num_frames
+    }
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.num_frames == 0
+    }
+    pub fn get(&self, i: usize) -> Option<Frame<'a>> {
+        if i >= self.num_frames {
+            return None;
+        }
+        let frame = &self.data[i * FRAME_SIZE..(i + 1) * FRAME_SIZE];
+        Some(Frame {
+            delay_ms: u16::from_le_bytes([frame[0], frame[1]]),
+            rgb: &frame[FRAME_HEADER_SIZE..],
+        })
+    }
+}
+
+impl Frame<'_> {
+    // leds the transform moves off the matrix are left black
+    pub fn write_to(&self, led_data: &mut [RGB8; Matrix::NUM_LEDS]) {
+        led_data.fill(RGB8::default());
+        for (v, rgb) in matrix::LED_COORDS.iter().zip(self.rgb.chunks_exact(3)) {
+            matrix::set_matrix_oddr(*v, RGB8::new(rgb[0], rgb[1], rgb[2]), led_data);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matrix::{led_addr_oddr, set_transform, Transform};
+    use std::vec::Vec;
+
+    #[test]
+    fn write_to_follows_transform() {
+        let _lock = matrix::lock_transform();
+        let mut data = Vec::from(header(1));
+        data.extend_from_slice(&0u16.to_le_bytes());
+        for i in 0..Matrix::NUM_LEDS {
+            data.extend_from_slice(&[i as u8, (i >> 8) as u8, 1]);
+        }
+        let frames = Frames::new(&data).unwrap();
+        let frame = frames.get(0).unwrap();
+        let mut leds = [RGB8::default(); Matrix::NUM_LEDS];
+
+        frame.write_to(&mut leds);
+        for (i, c) in leds.iter().enumerate() {
+            assert_eq!(*c, RGB8::new(i as u8, (i >> 8) as u8, 1));
+        }
+
+        set_transform(Transform {
+            rotation: 1,
+            mirror: true,
+        });
+        frame.write_to(&mut leds);
+        for (i, v) in matrix::LED_COORDS.iter().enumerate() {
+            if let Ok(led) = led_addr_oddr(*v) {
+                assert_eq!(leds[led], RGB8::new(i as u8, (i >> 8) as u8, 1));
+            }
+        }
+        set_transform(Transform::default());
+    }
+}
@@ -3,8 +3,21 @@ use core::ops;
 use num_traits::{self, float::FloatCore, Num};
 
 // mostly based on https://www.redblobgames.com/grids/hexagons/
+//
+// Three coordinate systems are used for the same cells:
+// - OddR: offset coordinates, odd rows shifted right by half a cell. Maps directly to the
+//   matrix rows, used for addressing leds (set_matrix_oddr etc.).
+// - Axial: (q, r), the two independent cube coordinates. Used where a plain 2d lattice is
+//   needed (polygon fill, pixel conversion).
+// - Cube: (x, y, z) with x + y + z == 0, used for all geometry (distance, rotation,
+//   directions).
+// Conversions between all of them are lossless. Neighbors are always enumerated in
+// CUBE_DIRECTIONS order, see Neighbors.
 
-#[derive(Default, Debug, Clone, Copy)]
+// odd-r offset coordinates
+pub type OddR = Vec2;
+
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub struct Cube {
     pub x: i32,
     pub y: i32,
@@ -12,12 +25,88 @@ pub struct Cube {
 }
 
 impl Cube {
-    pub fn new(x: i32, y: i32, z: i32) -> Cube {
+    pub const fn new(x: i32, y: i32, z: i32) -> Cube {
         Cube { x, y, z }
     }
+    // const version of From<Vec2>
+    pub const fn from_oddr(v: Vec2) -> Cube {
+        let x = v.x - (v.y - (v.y & 1)) / 2;
+        let z = v.y;
+        Cube { x, y: -x - z, z }
+    }
+    // const version of Into<Vec2>
+    pub const fn to_oddr(self) -> Vec2 {
+        Vec2::new(self.x + (self.z - (self.z & 1)) / 2, self.z)
+    }
     pub fn zero() -> Cube {
         Cube::default()
     }
+    pub fn distance(&self, other: &Cube) -> i32 {
+        cube_distance(self, other)
+    }
+    // rotate around the origin in 60° steps, positive is clockwise on the matrix
+    pub fn rotate(&self, steps: i32) -> Cube {
+        match steps.rem_euclid(6) {
+            0 => *self,
+            1 => Cube::new(-self.z, -self.x, -self.y),
+            2 => Cube::new(self.y, self.z, self.x),
+            3 => Cube::new(-self.x, -self.y, -self.z),
+            4 => Cube::new(self.z, self.x, self.y),
+            _ => Cube::new(-self.y, -self.z, -self.x),
+        }
+    }
+    // mirror left / right (along the vertical axis through the origin)
+    pub fn mirror(&self) -> Cube {
+        Cube::new(self.y, self.x, self.z)
+    }
+    // cell center in cartesian space, scaled so that adjacent cells are 1.0 apart
+    pub fn to_pixel(self) -> (f32, f32) {
+        (self.x as f32 + self.z as f32 * 0.5, self.z as f32 * SQRT3_2)
+    }
+}
+
+pub const SQRT3_2: f32 = 0.866_025_4;
+
+// inverse of Cube::to_pixel, yields fractional axial (q, r) coordinates
+pub fn pixel_to_axial(x: f32, y: f32) -> (f32, f32) {
+    let r = y / SQRT3_2;
+    (x - r * 0.5, r)
+}
+
+// cell containing the point (x, y) in pixel space
+pub fn pixel_to_cube(x: f32, y: f32) -> Cube {
+    let (q, r) = pixel_to_axial(x, y);
+    cube_round(q, -q - r, r)
+}
+
+// Distribute a fractional axial position onto the three surrounding cell centers
+// (barycentric weights, summing up to 1.0). Cell centers form a triangle lattice, and
+// axial space is an affine image of it, so the weights can be computed there directly.
+pub fn axial_split(q: f32, r: f32) -> [(Cube, f32); 3] {
+    let q0 = q.floor();
+    let r0 = r.floor();
+    let fq = q - q0;
+    let fr = r - r0;
+    let cell = |dq: i32, dr: i32| -> Cube {
+        Axial {
+            q: q0 as i32 + dq,
+            r: r0 as i32 + dr,
+        }
+        .into()
+    };
+    if fq + fr <= 1.0 {
+        [
+            (cell(0, 0), 1.0 - fq - fr),
+            (cell(1, 0), fq),
+            (cell(0, 1), fr),
+        ]
+    } else {
+        [
+            (cell(1, 1), fq + fr - 1.0),
+            (cell(1, 0), 1.0 - fr),
+            (cell(0, 1), 1.0 - fq),
+        ]
+    }
 }
 
 impl From<&Cube> for Cube {
@@ -70,19 +159,20 @@ impl From<(i32, i32, i32)> for Cube {
     }
 }
 
-pub struct Hex {
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Axial {
     pub q: i32,
     pub r: i32,
 }
 
-impl From<Cube> for Hex {
+impl From<Cube> for Axial {
     fn from(c: Cube) -> Self {
-        Hex { q: c.x, r: c.z }
+        Axial { q: c.x, r: c.z }
     }
 }
 
-impl From<Hex> for Cube {
-    fn from(h: Hex) -> Self {
+impl From<Axial> for Cube {
+    fn from(h: Axial) -> Self {
         Cube {
             x: h.q,
             y: -h.q - h.r,
@@ -99,12 +189,21 @@ impl From<Hex> for Cube {
 //     }
 // }
 
-impl From<Vec2> for Cube {
-    fn from(v: Vec2) -> Cube {
-        let x = v.x - (v.y - (v.y & 1)) / 2;
-        let z = v.y;
-        let y = -x - z;
-        Cube { x, y, z }
+impl From<OddR> for Cube {
+    fn from(v: OddR) -> Cube {
+        Cube::from_oddr(v)
+    }
+}
+
+impl From<OddR> for Axial {
+    fn from(v: OddR) -> Axial {
+        Cube::from_oddr(v).into()
+    }
+}
+
+impl From<Axial> for OddR {
+    fn from(h: Axial) -> OddR {
+        Cube::from(h).to_oddr()
     }
 }
 
@@ -117,6 +216,34 @@ pub const CUBE_DIRECTIONS: [Cube; 6] = [
     Cube { x: 0, y: -1, z: 1 },
 ];
 
+// The six neighbors of a cell, in the same order for every coordinate system:
+// direction d is CUBE_DIRECTIONS[d % 6] (0 is east, counting counter-clockwise on the
+// matrix).
+pub trait Neighbors: Copy {
+    fn neighbor(self, direction: usize) -> Self;
+    fn neighbors(self) -> [Self; 6] {
+        core::array::from_fn(|d| self.neighbor(d))
+    }
+}
+
+impl Neighbors for Cube {
+    fn neighbor(self, direction: usize) -> Cube {
+        self + CUBE_DIRECTIONS[direction % 6]
+    }
+}
+
+impl Neighbors for Axial {
+    fn neighbor(self, direction: usize) -> Axial {
+        Cube::from(self).neighbor(direction).into()
+    }
+}
+
+impl Neighbors for OddR {
+    fn neighbor(self, direction: usize) -> OddR {
+        Cube::from_oddr(self).neighbor(direction).to_oddr()
+    }
+}
+
 fn lerp<T: Num + Copy>(a: T, b: T, t: T) -> T {
     a + (b - a) * t
 }
@@ -154,7 +281,7 @@ fn cube_round(x: f32, y: f32, z: f32) -> Cube {
 }
 
 fn cube_distance(a: &Cube, b: &Cube) -> i32 {
-    (a.x - b.x).abs() + (a.y - b.y).abs() + (a.z - b.z).abs() / 2
+    ((a.x - b.x).abs() + (a.y - b.y).abs() + (a.z - b.z).abs()) / 2
 }
 
 pub fn cube_linedraw(a: &Cube, b: &Cube) -> (i32, [Cube; 20]) {
@@ -199,6 +326,176 @@ impl Iterator for CubeLinedraw {
     }
 }
 
+// all cells at exactly `radius` steps from `center`, walking the ring counter-clockwise
+// starting at the south-west corner (radius 0 yields just the center)
+pub struct Ring {
+    cur: Cube,
+    radius: i32,
+    side: usize,
+    step: i32,
+}
+
+impl Ring {
+    pub fn new(center: Cube, radius: i32) -> Self {
+        Ring {
+            cur: center + CUBE_DIRECTIONS[4] * radius,
+            radius,
+            side: 0,
+            step: 0,
+        }
+    }
+}
+
+impl Iterator for Ring {
+    type Item = Cube;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.radius < 0 || self.side >= 6 {
+            return None;
+        }
+        let c = self.cur;
+        if self.radius == 0 {
+            self.side = 6;
+            return Some(c);
+        }
+        self.cur = self.cur.neighbor(self.side);
+        self.step += 1;
+        if self.step >= self.radius {
+            self.step = 0;
+            self.side += 1;
+        }
+        Some(c)
+    }
+}
+
+// all cells within `radius` steps from `center`, ring by ring from the inside out
+pub struct Spiral {
+    ring: Ring,
+    center: Cube,
+    radius: i32,
+}
+
+impl Spiral {
+    pub fn new(center: Cube, radius: i32) -> Self {
+        Spiral {
+            ring: Ring::new(center, 0),
+            center,
+            radius,
+        }
+    }
+}
+
+impl Iterator for Spiral {
+    type Item = Cube;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.ring.radius > self.radius {
+                return None;
+            }
+            if let Some(c) = self.ring.next() {
+                return Some(c);
+            }
+            self.ring = Ring::new(self.center, self.ring.radius + 1);
+        }
+    }
+}
+
 pub mod prelude {
-    pub use super::{Cube, Hex};
+    pub use super::{Axial, Cube, Neighbors, OddR, Ring, Spiral};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells() -> impl Iterator<Item = OddR> {
+        (-7..=7).flat_map(|y| (-7..=7).map(move |x| OddR::new(x, y)))
+    }
+
+    #[test]
+    fn conversions_roundtrip() {
+        for v in cells() {
+            let c = Cube::from(v);
+            assert_eq!(c.x + c.y + c.z, 0);
+            assert_eq!(OddR::from(c), v);
+            assert_eq!(c.to_oddr(), v);
+            assert_eq!(Cube::from(Axial::from(c)), c);
+            assert_eq!(OddR::from(Axial::from(v)), v);
+        }
+    }
+
+    #[test]
+    fn neighbors_match_cube_directions() {
+        for v in cells() {
+            let c = Cube::from(v);
+            let a = Axial::from(v);
+            for (d, dir) in CUBE_DIRECTIONS.iter().enumerate() {
+                assert_eq!(c.neighbor(d), c + *dir);
+                assert_eq!(Cube::from(v.neighbor(d)), c + *dir);
+                assert_eq!(Cube::from(a.neighbor(d)), c + *dir);
+                assert_eq!(c.neighbor(d).distance(&c), 1);
+            }
+            assert_eq!(v.neighbors()[4], v.neighbor(10));
+        }
+    }
+
+    // the offset table formerly used by matrix::adjacent yields the same cells
+    #[test]
+    fn neighbors_match_oddr_offsets() {
+        for v in cells() {
+            let xshift = v.y.abs() % 2;
+            let offsets = [
+                (1, 0),
+                (-1, 0),
+                (-1 + xshift, 1),
+                (xshift, 1),
+                (-1 + xshift, -1),
+                (xshift, -1),
+            ];
+            let n = v.neighbors();
+            for (dx, dy) in offsets {
+                assert!(n.contains(&OddR::new(v.x + dx, v.y + dy)));
+            }
+        }
+    }
+
+    #[test]
+    fn distance() {
+        let o = Cube::zero();
+        assert_eq!(o.distance(&Cube::new(3, -3, 0)), 3);
+        assert_eq!(o.distance(&Cube::new(2, 1, -3)), 3);
+        assert_eq!(Cube::new(1, -2, 1).distance(&Cube::new(-2, 1, 1)), 3);
+        for d in 0..6 {
+            assert_eq!(o.distance(&o.neighbor(d)), 1);
+        }
+        // a line is one cell per step, each adjacent to the previous one
+        let (a, b) = (Cube::new(-2, 3, -1), Cube::new(4, -1, -3));
+        let line = CubeLinedraw::new(a, b).collect::<std::vec::Vec<_>>();
+        assert_eq!(line.len() as i32, a.distance(&b));
+        assert_eq!(line[0], a);
+        for w in line.windows(2) {
+            assert_eq!(w[0].distance(&w[1]), 1);
+        }
+        assert_eq!(line.last().unwrap().distance(&b), 1);
+    }
+
+    #[test]
+    fn ring_and_spiral() {
+        let center = Cube::new(2, -3, 1);
+        for r in 0..6 {
+            let ring = Ring::new(center, r).collect::<std::vec::Vec<_>>();
+            assert_eq!(ring.len() as i32, (6 * r).max(1));
+            assert!(ring.iter().all(|c| c.distance(&center) == r));
+            assert_eq!(
+                ring.iter().collect::<std::collections::HashSet<_>>().len(),
+                ring.len()
+            );
+
+            let spiral = Spiral::new(center, r).collect::<std::collections::HashSet<_>>();
+            assert_eq!(spiral.len() as i32, 3 * r * r + 3 * r + 1);
+            assert!(spiral.iter().all(|c| c.distance(&center) <= r));
+        }
+        assert_eq!(Ring::new(center, -1).count(), 0);
+    }
 }
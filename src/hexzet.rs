@@ -0,0 +1,248 @@
+use core::iter::FromIterator;
+
+use crate::hex::{Axial, Cube};
+
+// Bit set of hex cells, keyed by Cube. The Bitzet counterpart for hex grids: instead of a
+// z-order curve it stores one word per row in axial coordinates, so all six neighbors of a
+// cell are at fixed offsets (one bit left / right in the same row or the rows above and
+// below) and whole rows can be processed with word-level bit operations, see
+// neighbor_counts.
+//
+// Covers axial q and r in -SIZE / 2..SIZE / 2, a rhombus which contains the hexagon of
+// radius SIZE / 2 - 1 around the origin.
+
+pub const SIZE: usize = 32;
+const HALF: i32 = SIZE as i32 / 2;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    OutOfRange(Cube),
+}
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct HexZet {
+    // bit q + SIZE / 2 of rows[r + SIZE / 2]
+    pub rows: [u32; SIZE],
+}
+
+impl HexZet {
+    pub const fn new() -> HexZet {
+        HexZet { rows: [0; SIZE] }
+    }
+
+    // (row, bit) of c
+    pub fn index(c: &Cube) -> Option<(usize, usize)> {
+        let a = Axial::from(*c);
+        let (x, y) = (a.q + HALF, a.r + HALF);
+        if x < 0 || x >= SIZE as i32 || y < 0 || y >= SIZE as i32 {
+            return None;
+        }
+        Some((y as usize, x as usize))
+    }
+
+    // inverse of index
+    pub fn cube(row: usize, bit: usize) -> Cube {
+        Axial {
+            q: bit as i32 - HALF,
+            r: row as i32 - HALF,
+        }
+        .into()
+    }
+
+    // panics if c is out of range
+    pub fn insert(&mut self, c: Cube) {
+        self.try_insert(c).expect("hexzet range exceeded");
+    }
+    pub fn try_insert(&mut self, c: Cube) -> Result<(), Error> {
+        let (row, bit) = Self::index(&c).ok_or(Error::OutOfRange(c))?;
+        self.rows[row] |= 1 << bit;
+        Ok(())
+    }
+    pub fn remove(&mut self, c: &Cube) {
+        if let Some((row, bit)) = Self::index(c) {
+            self.rows[row] &= !(1 << bit);
+        }
+    }
+    pub fn contains(&self, c: &Cube) -> bool {
+        Self::index(c).is_some_and(|(row, bit)| self.rows[row] & (1 << bit) != 0)
+    }
+    pub fn len(&self) -> usize {
+        self.rows.iter().map(|r| r.count_ones() as usize).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|r| *r == 0)
+    }
+    pub fn clear(&mut self) {
+        self.rows.fill(0);
+    }
+
+    // row by row, skipping empty words
+    pub fn iter(&self) -> impl Iterator<Item = Cube> + '_ {
+        self.rows.iter().enumerate().flat_map(|(row, word)| {
+            let mut word = *word;
+            core::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(Self::cube(row, bit))
+            })
+        })
+    }
+
+    // Number of members among the six neighbors, for every cell at once. Outside of the
+    // covered area counts as empty, or with wrap the opposite edges are connected.
+    pub fn neighbor_counts(&self, wrap: bool) -> NeighborCounts {
+        let row = |r: isize| -> u32 {
+            if wrap {
+                self.rows[r.rem_euclid(SIZE as isize) as usize]
+            } else if r < 0 || r >= SIZE as isize {
+                0
+            } else {
+                self.rows[r as usize]
+            }
+        };
+        // neighbor at q + 1 / q - 1 moved onto bit q
+        let east = |w: u32| if wrap { w.rotate_right(1) } else { w >> 1 };
+        let west = |w: u32| if wrap { w.rotate_left(1) } else { w << 1 };
+
+        let mut planes = [[0; 3]; SIZE];
+        for (r, out) in planes.iter_mut().enumerate() {
+            let r = r as isize;
+            let (above, here, below) = (row(r - 1), row(r), row(r + 1));
+            // in CUBE_DIRECTIONS order
+            let n = [
+                east(here),
+                east(above),
+                above,
+                west(here),
+                west(below),
+                below,
+            ];
+            // bit sliced sum of six one bit inputs
+            let (s0, c0) = full_add(n[0], n[1], n[2]);
+            let (s1, c1) = full_add(n[3], n[4], n[5]);
+            let (b1, c2) = full_add(c0, c1, s0 & s1);
+            *out = [s0 ^ s1, b1, c2];
+        }
+        NeighborCounts { planes }
+    }
+}
+
+fn full_add(a: u32, b: u32, c: u32) -> (u32, u32) {
+    (a ^ b ^ c, (a & b) | (c & (a ^ b)))
+}
+
+// Result of HexZet::neighbor_counts. Bit sliced: bit k of the count of a cell is in
+// planes[row][k], at the cell's bit.
+pub struct NeighborCounts {
+    pub planes: [[u32; 3]; SIZE],
+}
+
+impl NeighborCounts {
+    pub fn get(&self, c: &Cube) -> u8 {
+        HexZet::index(c).map_or(0, |(row, bit)| {
+            let p = &self.planes[row];
+            (((p[0] >> bit) & 1) | (((p[1] >> bit) & 1) << 1) | (((p[2] >> bit) & 1) << 2)) as u8
+        })
+    }
+
+    // cells of row whose count n has bit n set in counts
+    pub fn matching(&self, row: usize, counts: u8) -> u32 {
+        let p = &self.planes[row];
+        let mut out = 0;
+        for n in 0..=6 {
+            if counts & (1 << n) != 0 {
+                let bit = |k: usize| if n & (1 << k) != 0 { p[k] } else { !p[k] };
+                out |= bit(0) & bit(1) & bit(2);
+            }
+        }
+        out
+    }
+}
+
+impl FromIterator<Cube> for HexZet {
+    fn from_iter<T: IntoIterator<Item = Cube>>(iter: T) -> Self {
+        let mut hz = HexZet::new();
+        iter.into_iter().for_each(|c| hz.insert(c));
+        hz
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex::CUBE_DIRECTIONS;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    // count the six neighbors one by one
+    fn naive_count(s: &HexZet, c: Cube, wrap: bool) -> u8 {
+        CUBE_DIRECTIONS
+            .iter()
+            .map(|d| {
+                let mut n = Axial::from(c + *d);
+                if wrap {
+                    n.q = (n.q + HALF).rem_euclid(SIZE as i32) - HALF;
+                    n.r = (n.r + HALF).rem_euclid(SIZE as i32) - HALF;
+                }
+                s.contains(&n.into()) as u8
+            })
+            .sum()
+    }
+
+    fn check(s: &HexZet) {
+        for wrap in [false, true] {
+            let counts = s.neighbor_counts(wrap);
+            let mut expected = [[0; SIZE]; SIZE];
+            for (row, out) in expected.iter_mut().enumerate() {
+                for (bit, n) in out.iter_mut().enumerate() {
+                    *n = naive_count(s, HexZet::cube(row, bit), wrap);
+                    assert_eq!(counts.get(&HexZet::cube(row, bit)), *n);
+                }
+            }
+            for mask in 0..128u8 {
+                for (row, expected) in expected.iter().enumerate() {
+                    let bits = (0..SIZE)
+                        .filter(|bit| mask & (1 << expected[*bit]) != 0)
+                        .fold(0, |w, bit| w | (1 << bit));
+                    assert_eq!(counts.matching(row, mask), bits, "{} {}", mask, row);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn neighbor_counts_random() {
+        let mut rng = SmallRng::seed_from_u64(7);
+        for density in [0.05, 0.3, 0.5, 0.8] {
+            let mut s = HexZet::new();
+            for row in s.rows.iter_mut() {
+                *row = (0..SIZE).fold(0, |w, bit| w | ((rng.gen_bool(density) as u32) << bit));
+            }
+            check(&s);
+        }
+    }
+
+    #[test]
+    fn neighbor_counts_edges() {
+        let last = SIZE - 1;
+        // all cells, only the border, single corners and an edge in the middle
+        let full = HexZet { rows: [!0; SIZE] };
+        let mut border = HexZet::new();
+        for (row, w) in border.rows.iter_mut().enumerate() {
+            *w = if row == 0 || row == last {
+                !0
+            } else {
+                1 | (1 << last)
+            };
+        }
+        check(&full);
+        check(&border);
+        for (row, bit) in [(0, 0), (0, last), (last, 0), (last, last), (0, SIZE / 2)] {
+            let mut s = HexZet::new();
+            s.rows[row] = 1 << bit;
+            check(&s);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use core::ops::Range;
+
+use crate::{
+    hex::{Cube, CUBE_DIRECTIONS},
+    matrix::{map_from_xy, row, Corner, Layout, Shape},
+};
+
+// Implements Shape for $shape from a row based Layout.
+macro_rules! layout_shape {
+    ($shape:ident, $layout:expr) => {
+        impl $shape {
+            const LAYOUT: Layout = $layout;
+            const XY: [(u8, u8); Self::LAYOUT.num_leds()] = Self::LAYOUT.led_xy();
+            const MAP_TABLE: [i16; Self::LAYOUT.width * Self::LAYOUT.height()] =
+                map_from_xy(Self::LAYOUT.width, &Self::XY);
+            const ZONE_TABLE: [Range<usize>; Self::LAYOUT.zone_rows.len()] = Self::LAYOUT.zones();
+        }
+
+        impl Shape for $shape {
+            const WIDTH: usize = Self::LAYOUT.width;
+            const HEIGHT: usize = Self::LAYOUT.height();
+            const ORIGIN: (usize, usize) = Self::LAYOUT.origin;
+            const MAP: &'static [i16] = &Self::MAP_TABLE;
+            const LED_XY: &'static [(u8, u8)] = &Self::XY;
+            const ZONES: &'static [Range<usize>] = &Self::ZONE_TABLE;
+        }
+    };
+}
+
+// The original mocca matrix: 291 leds in a hexagon with 8 leds per side, corners cut off
+pub struct Hexagon;
+
+layout_shape!(
+    Hexagon,
+    Layout {
+        width: 19,
+        rows: &[
+            row(8, 8),
+            row(7, 9),
+            row(7, 10),
+            row(6, 11),
+            row(3, 15),
+            row(2, 16),
+            row(2, 17),
+            row(1, 17),
+            row(1, 17),
+            row(0, 17),
+            row(0, 17),
+            row(0, 17),
+            row(1, 17),
+            row(1, 17),
+            row(2, 17),
+            row(2, 16),
+            row(3, 15),
+            row(6, 11),
+            row(7, 10),
+            row(7, 9),
+            row(8, 8),
+        ],
+        serpentine: true,
+        start: Corner::TopLeft,
+        origin: (10, 10),
+        zone_rows: &[7, 4, 4, 6],
+    }
+);
+
+// 16x16 serpentine panel, seen as hex grid with every odd row shifted by half a led
+pub struct Rectangle;
+
+layout_shape!(
+    Rectangle,
+    Layout {
+        width: 16,
+        rows: &[row(0, 16); 16],
+        serpentine: true,
+        start: Corner::TopLeft,
+        origin: (8, 8),
+        zone_rows: &[8, 8],
+    }
+);
+
+// Single hex ring of RING_RADIUS (6 * RING_RADIUS leds), wired counter-clockwise from the
+// bottom left corner, like hex::Ring.
+pub struct Ring;
+
+pub const RING_RADIUS: usize = 4;
+const RING_LEDS: usize = 6 * RING_RADIUS;
+const RING_SIZE: usize = 2 * RING_RADIUS + 1;
+// the center is the origin, which has to sit on an even row
+const _: () = assert!(RING_RADIUS.is_multiple_of(2));
+
+const fn ring_xy() -> [(u8, u8); RING_LEDS] {
+    let r = RING_RADIUS as i32;
+    let mut xy = [(0, 0); RING_LEDS];
+    let mut cur = Cube::new(
+        CUBE_DIRECTIONS[4].x * r,
+        CUBE_DIRECTIONS[4].y * r,
+        CUBE_DIRECTIONS[4].z * r,
+    );
+    let mut led = 0;
+    while led < RING_LEDS {
+        let v = cur.to_oddr();
+        xy[led] = ((v.x + r) as u8, (v.y + r) as u8);
+        let d = CUBE_DIRECTIONS[led / RING_RADIUS];
+        cur = Cube::new(cur.x + d.x, cur.y + d.y, cur.z + d.z);
+        led += 1;
+    }
+    xy
+}
+
+impl Ring {
+    const XY: [(u8, u8); RING_LEDS] = ring_xy();
+    const MAP_TABLE: [i16; RING_SIZE * RING_SIZE] = map_from_xy(RING_SIZE, &Self::XY);
+    // a single power zone
+    const ZONE_TABLE: [Range<usize>; 1] = [Range {
+        start: 0,
+        end: RING_LEDS,
+    }];
+}
+
+impl Shape for Ring {
+    const WIDTH: usize = RING_SIZE;
+    const HEIGHT: usize = RING_SIZE;
+    const ORIGIN: (usize, usize) = (RING_RADIUS, RING_RADIUS);
+    const MAP: &'static [i16] = &Self::MAP_TABLE;
+    const LED_XY: &'static [(u8, u8)] = &Self::XY;
+    const ZONES: &'static [Range<usize>] = &Self::ZONE_TABLE;
+}
@@ -1,26 +1,42 @@
 #![no_std]
+#[cfg(test)]
+extern crate std;
 pub use smart_leds::RGB8;
 pub mod app;
 pub mod bitzet;
 pub mod canvas;
 pub mod color;
+pub mod compositor;
 pub mod effects;
+pub mod field;
+pub mod font;
+pub mod frames;
 pub mod hex;
+pub mod hexzet;
+#[cfg(feature = "firmware")]
 pub mod i2s;
+pub mod layouts;
+pub mod life;
 pub mod math;
 pub mod matrix;
+pub mod oklab;
+pub mod output;
+pub mod palette;
+pub mod pattern;
 pub mod power_zones;
+pub mod sprite;
+#[cfg(feature = "firmware")]
 pub mod ws2812;
 
 pub mod prelude {
     pub use super::{
         app::{self, App, Env},
         canvas::{self, Canvas},
-        color::{self, Rainbow, HV8},
+        color::{self, Rainbow, HSV8, HV8},
+        hex::Neighbors,
         math::Vec2,
         matrix::{
-            adjacent, get_matrix, led_addr, set_matrix, set_matrix_oddr, Error, MATRIX_HEIGHT,
-            MATRIX_WIDTH, NUM_LEDS,
+            get_matrix, led_addr, led_addr_oddr, set_matrix, set_matrix_oddr, Error, Matrix, Shape,
         },
         RGB8,
     };
@@ -0,0 +1,333 @@
+use crate::{
+    hex::{Axial, Cube},
+    hexzet::{self, HexZet},
+    math::Vec2,
+};
+
+// Cellular automata on the hex grid with life like rules (birth / survival by number of
+// live neighbors, 0..=6) and their multi-state Generations variant.
+
+// side length of the simulated area, axial q and r in -GRID / 2..GRID / 2 (a rhombus, see
+// HexZet). Wrapping around in axial coordinates has no seam.
+pub const GRID: usize = hexzet::SIZE;
+const HALF: i32 = GRID as i32 / 2;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    Syntax,
+    // neighbor counts go from 0 to 6
+    NeighborCount,
+    // need at least 2 states (dead and alive)
+    States,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rule {
+    // bit n set: a dead cell with n live neighbors is born
+    pub birth: u8,
+    // bit n set: a live cell with n live neighbors stays alive
+    pub survive: u8,
+    // Number of states, including dead (0) and alive (1). With more than two (Generations
+    // rules) a cell that does not survive ages through the states 2..states before it is
+    // dead again. Aging cells do not count as neighbors and can not be born into.
+    pub states: u8,
+}
+
+impl Rule {
+    // the rule Hexlife2 started out with
+    pub const HEXLIFE: Rule = Rule {
+        birth: 1 << 2,
+        survive: (1 << 1) | (1 << 2),
+        states: 2,
+    };
+
+    // "B2/S12" for life like rules, "B2/S12/C5" (or "B2/S12/5") for Generations
+    pub fn parse(rule: &str) -> Result<Rule, Error> {
+        let mut parts = rule.trim().split('/');
+        let (Some(birth), Some(survive)) = (parts.next(), parts.next()) else {
+            return Err(Error::Syntax);
+        };
+        let birth = birth.strip_prefix(['B', 'b']).ok_or(Error::Syntax)?;
+        let survive = survive.strip_prefix(['S', 's']).ok_or(Error::Syntax)?;
+        let states = match parts.next() {
+            Some(states) => {
+                let states = states.strip_prefix(['C', 'c']).unwrap_or(states);
+                states.parse().map_err(|_| Error::Syntax)?
+            }
+            None => 2,
+        };
+        if parts.next().is_some() {
+            return Err(Error::Syntax);
+        }
+        if states < 2 {
+            return Err(Error::States);
+        }
+        Ok(Rule {
+            birth: neighbor_counts(birth)?,
+            survive: neighbor_counts(survive)?,
+            states,
+        })
+    }
+
+    // state of a cell in the next generation
+    pub fn next(&self, state: u8, live_neighbors: usize) -> u8 {
+        match state {
+            0 if self.birth & (1 << live_neighbors) != 0 => 1,
+            0 => 0,
+            1 if self.survive & (1 << live_neighbors) != 0 => 1,
+            s if s + 1 < self.states => s + 1,
+            _ => 0,
+        }
+    }
+}
+
+fn neighbor_counts(s: &str) -> Result<u8, Error> {
+    let mut mask = 0;
+    for c in s.chars() {
+        let n = c.to_digit(10).ok_or(Error::Syntax)?;
+        if n > 6 {
+            return Err(Error::NeighborCount);
+        }
+        mask |= 1 << n;
+    }
+    Ok(mask)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Boundary {
+    // everything outside of the grid is dead
+    Dead,
+    // opposite edges are connected
+    Wrap,
+}
+
+#[derive(Clone)]
+pub struct Automaton {
+    pub rule: Rule,
+    pub boundary: Boundary,
+    alive: HexZet,
+    // cells in states 2.. (Generations), their state is in ages
+    aging: HexZet,
+    ages: [u8; GRID * GRID],
+}
+
+impl Automaton {
+    pub fn new(rule: Rule, boundary: Boundary) -> Automaton {
+        Automaton {
+            rule,
+            boundary,
+            alive: HexZet::new(),
+            aging: HexZet::new(),
+            ages: [0; GRID * GRID],
+        }
+    }
+
+    // cell of v inside the grid
+    fn cell(&self, v: Vec2) -> Option<Cube> {
+        let c = Cube::from_oddr(v);
+        match self.boundary {
+            Boundary::Dead => HexZet::index(&c).map(|_| c),
+            Boundary::Wrap => {
+                let a = Axial::from(c);
+                let wrap = |i: i32| (i + HALF).rem_euclid(GRID as i32) - HALF;
+                Some(
+                    Axial {
+                        q: wrap(a.q),
+                        r: wrap(a.r),
+                    }
+                    .into(),
+                )
+            }
+        }
+    }
+
+    fn age_index(c: &Cube) -> usize {
+        let (row, bit) = HexZet::index(c).unwrap_or_default();
+        row * GRID + bit
+    }
+
+    // 0: dead, 1: alive, 2..: aging (Generations)
+    pub fn state(&self, v: Vec2) -> u8 {
+        match self.cell(v) {
+            Some(c) if self.alive.contains(&c) => 1,
+            Some(c) if self.aging.contains(&c) => self.ages[Self::age_index(&c)],
+            _ => 0,
+        }
+    }
+
+    pub fn is_alive(&self, v: Vec2) -> bool {
+        self.cell(v).is_some_and(|c| self.alive.contains(&c))
+    }
+
+    // ignored outside of the grid (with Boundary::Dead)
+    pub fn set(&mut self, v: Vec2, state: u8) {
+        let Some(c) = self.cell(v) else {
+            return;
+        };
+        self.alive.remove(&c);
+        self.aging.remove(&c);
+        match state {
+            0 => (),
+            1 => self.alive.insert(c),
+            s => {
+                self.aging.insert(c);
+                self.ages[Self::age_index(&c)] = s;
+            }
+        }
+    }
+
+    // switch between dead and alive
+    pub fn toggle(&mut self, v: Vec2) {
+        let state = if self.is_alive(v) { 0 } else { 1 };
+        self.set(v, state);
+    }
+
+    pub fn clear(&mut self) {
+        self.alive.clear();
+        self.aging.clear();
+    }
+
+    pub fn step(&mut self) {
+        let counts = self.alive.neighbor_counts(self.boundary == Boundary::Wrap);
+        let mut alive = HexZet::new();
+        let mut aging = HexZet::new();
+        for r in 0..GRID {
+            let (now, old) = (self.alive.rows[r], self.aging.rows[r]);
+            let survive = now & counts.matching(r, self.rule.survive);
+            let born = !now & !old & counts.matching(r, self.rule.birth);
+            alive.rows[r] = survive | born;
+            if self.rule.states <= 2 {
+                continue;
+            }
+            // cells that stopped being alive start aging, until they reach states
+            let mut start = now & !survive;
+            let mut old = old;
+            while old != 0 {
+                let bit = old.trailing_zeros() as usize;
+                old &= old - 1;
+                let age = &mut self.ages[r * GRID + bit];
+                *age += 1;
+                if *age < self.rule.states {
+                    aging.rows[r] |= 1 << bit;
+                }
+            }
+            while start != 0 {
+                let bit = start.trailing_zeros() as usize;
+                start &= start - 1;
+                self.ages[r * GRID + bit] = 2;
+                aging.rows[r] |= 1 << bit;
+            }
+        }
+        self.alive = alive;
+        self.aging = aging;
+    }
+
+    // (position, state) of all cells that are not dead
+    pub fn iter(&self) -> impl Iterator<Item = (Vec2, u8)> + '_ {
+        let alive = self.alive.iter().map(|c| (c.to_oddr(), 1));
+        let aging = self
+            .aging
+            .iter()
+            .map(|c| (c.to_oddr(), self.ages[Self::age_index(&c)]));
+        alive.chain(aging)
+    }
+
+    pub fn population(&self) -> usize {
+        self.alive.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hex::Neighbors;
+
+    #[test]
+    fn parse_rules() {
+        assert_eq!(Rule::parse("B2/S12"), Ok(Rule::HEXLIFE));
+        assert_eq!(Rule::parse(" b2/s12 "), Ok(Rule::HEXLIFE));
+        let generations = Rule {
+            birth: (1 << 2) | (1 << 4),
+            survive: 1 << 3,
+            states: 5,
+        };
+        assert_eq!(Rule::parse("B24/S3/C5"), Ok(generations));
+        assert_eq!(Rule::parse("B24/S3/5"), Ok(generations));
+        // empty birth / survival sets
+        assert_eq!(
+            Rule::parse("B/S0"),
+            Ok(Rule {
+                birth: 0,
+                survive: 1,
+                states: 2
+            })
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for rule in [
+            "",
+            "B2",
+            "2/S12",
+            "B2/12",
+            "B2/S1x",
+            "B2/S12/C",
+            "B2/S12/C5/1",
+        ] {
+            assert_eq!(Rule::parse(rule), Err(Error::Syntax), "{}", rule);
+        }
+        assert_eq!(Rule::parse("B7/S12"), Err(Error::NeighborCount));
+        assert_eq!(Rule::parse("B2/S129"), Err(Error::NeighborCount));
+        assert_eq!(Rule::parse("B2/S12/C1"), Err(Error::States));
+        assert_eq!(Rule::parse("B2/S12/0"), Err(Error::States));
+    }
+
+    #[test]
+    fn next_state() {
+        let rule = Rule::HEXLIFE;
+        for n in 0..=6 {
+            assert_eq!(rule.next(0, n), (n == 2) as u8, "born with {}", n);
+            assert_eq!(
+                rule.next(1, n),
+                (n == 1 || n == 2) as u8,
+                "survive with {}",
+                n
+            );
+        }
+        let rule = Rule::parse("B2/S12/C4").unwrap();
+        assert_eq!(rule.next(1, 2), 1);
+        assert_eq!(rule.next(1, 3), 2);
+        // aging cells ignore their neighbors
+        assert_eq!(rule.next(2, 2), 3);
+        assert_eq!(rule.next(3, 2), 0);
+    }
+
+    #[test]
+    fn generations_aging() {
+        // nothing survives, cells age through 2 and 3 before they are dead
+        let mut life = Automaton::new(Rule::parse("B2/S/C4").unwrap(), Boundary::Dead);
+        let o = Vec2::new(0, 0);
+        life.set(o, 1);
+        for state in [2, 3, 0] {
+            life.step();
+            assert_eq!(life.state(o), state);
+            assert_eq!(life.population(), 0);
+        }
+        assert_eq!(life.iter().count(), 0);
+
+        // an aging cell between two live ones is not born into and does not count as a
+        // neighbor
+        let (a, b, c) = (o, o.neighbor(0), o.neighbor(1));
+        assert_eq!(c.neighbor(5), b);
+        life.set(a, 1);
+        life.set(b, 1);
+        life.set(c, 2);
+        life.step();
+        assert_eq!(life.state(c), 3);
+        // a and b die (no survival), the other common neighbor of a and b is born
+        assert_eq!((life.state(a), life.state(b)), (2, 2));
+        assert_eq!(life.state(o.neighbor(5)), 1);
+        assert_eq!(life.population(), 1);
+    }
+}
@@ -21,7 +21,11 @@ use embassy_sync::{
 };
 use embassy_time::{Duration, Instant, Ticker, Timer, TICK_HZ};
 use mocca_matrix_embassy::{
+    color::{BlendMode, RGB16},
+    compositor::Compositor,
     i2s::{PioI2S, PioI2SProgram},
+    matrix::{self, Transform},
+    output,
     power_zones::{self, DynamicLimit, NUM_ZONES},
     prelude::*,
     ws2812::{PioWs2812, PioWs2812Program},
@@ -34,10 +38,19 @@ use {defmt_rtt as _, panic_probe as _};
 const NUM_SAMPLES: usize = 32;
 static SAMPLES: Signal<CriticalSectionRawMutex, [i16; NUM_SAMPLES]> = Signal::new();
 
-static LEDS: Signal<CriticalSectionRawMutex, [RGB8; NUM_LEDS]> = Signal::new();
+static LEDS: Signal<CriticalSectionRawMutex, [RGB8; Matrix::NUM_LEDS]> = Signal::new();
 
 static ENV: Mutex<ThreadModeRawMutex, Env> = Mutex::new(Env { spl_db: 0.0 });
 
+// how the panel is mounted, can be changed at runtime with matrix::set_transform
+const MOUNTING: Transform = Transform {
+    rotation: 0,
+    mirror: false,
+};
+
+// gamma, color correction and brightness applied to every frame, see LedStrip::output
+const OUTPUT: output::Config = output::Config::DEFAULT;
+
 bind_interrupts!(struct Irqs0 {
     PIO0_IRQ_0 => InterruptHandler<PIO0>;
 });
@@ -56,20 +69,29 @@ async fn blink_task(mut led: Output<'static>) {
     }
 }
 pub struct LedStrip {
-    pub data: [RGB8; NUM_LEDS],
+    // linear values rendered by the apps, kept as they are between frames
+    pub data: [RGB16; Matrix::NUM_LEDS],
+    pub output: output::Output,
+    dither: output::Dither<{ Matrix::NUM_LEDS }>,
     dynamic_limit: [DynamicLimit; NUM_ZONES],
     count: u32,
 }
 impl LedStrip {
     pub fn new() -> Self {
         Self {
-            data: [RGB8::default(); NUM_LEDS],
+            data: [RGB16::default(); Matrix::NUM_LEDS],
+            output: output::Output::new(OUTPUT),
+            dither: output::Dither::new(),
             dynamic_limit: Default::default(),
             count: 0,
         }
     }
     pub async fn signal(&mut self) {
-        let led_strip_power = power_zones::estimate_current_all(&self.data);
+        let mut corrected = self.data;
+        self.output.apply16_all(&mut corrected);
+        let mut data = [RGB8::default(); Matrix::NUM_LEDS];
+        self.dither.apply(&corrected, &mut data);
+        let led_strip_power = power_zones::estimate_current_all(&data);
         let mut limit = [0u32; NUM_ZONES];
         for i in 0..NUM_ZONES {
             self.dynamic_limit[i].add_measurement(led_strip_power[i]);
@@ -80,9 +102,9 @@ impl LedStrip {
         }
 
         // info!("power: {:?} {:?}", led_strip_power, limit);
-        power_zones::limit_current(&mut self.data, &limit);
+        power_zones::limit_current(&mut data, &limit);
         self.count = self.count.wrapping_add(1);
-        LEDS.signal(self.data);
+        LEDS.signal(data);
     }
 }
 #[embassy_executor::task]
@@ -181,31 +203,44 @@ impl DebouncedSwitch {
 }
 #[embassy_executor::task]
 async fn rgb_task(switch: Input<'static>) {
+    matrix::set_transform(MOUNTING);
     let mut led_strip = LedStrip::new();
     let mut ticker = Ticker::every(Duration::from_millis(16));
     let mut splash = app::drawing::new();
     let mut hexlife = app::hexlife2::new();
     let mut fire = app::cellular::new();
-    let mut show_fire = false;
+    let mut vu = app::vu::new();
+    let mut marquee = app::marquee::new("MOCCA MATRIX");
+    // hexlife in the background, the sound level on top
+    let mut layers = Compositor::<2>::new();
+    layers.layers[1].mode = BlendMode::Screen;
+    // switch presses cycle through the modes
+    const MODES: usize = 4;
+    let mut mode = 0;
     let mut debounce = DebouncedSwitch::default();
     let mut ct: usize = 0;
     let mut dt_cum = Duration::default();
     loop {
         debounce.update(switch.is_low());
         if debounce.just_pressed {
-            show_fire = !show_fire;
+            mode = (mode + 1) % MODES;
         }
         // info!("switch: {}", switch.is_low());
         let start = Instant::now();
-        let app = if ct < 120 {
-            &mut splash as &mut dyn App
-        } else if show_fire {
-            &mut fire as &mut dyn App
-        } else {
-            &mut hexlife as &mut dyn App
-        };
         let env = ENV.lock().await.clone();
-        app.tick(&mut led_strip.data, &env);
+        if ct < 120 {
+            splash.tick16(&mut led_strip.data, &env);
+        } else {
+            match mode {
+                0 => hexlife.tick16(&mut led_strip.data, &env),
+                1 => {
+                    layers.tick([&mut hexlife as &mut dyn App, &mut vu], &env);
+                    layers.flatten(&mut led_strip.data);
+                }
+                2 => fire.tick16(&mut led_strip.data, &env),
+                _ => marquee.tick16(&mut led_strip.data, &env),
+            }
+        }
         let dt = start.elapsed();
 
         dt_cum += dt;
@@ -232,7 +267,7 @@ async fn rgb_simple() {
         r: 0u8,
         g: 0u8,
         b: 0u8,
-    }; NUM_LEDS];
+    }; Matrix::NUM_LEDS];
     loop {
         ticker.next().await;
 
@@ -243,7 +278,7 @@ async fn rgb_simple() {
 }
 
 #[embassy_executor::task]
-async fn rgb_writer_task(mut ws2812: PioWs2812<'static, PIO1, 1, NUM_LEDS>) {
+async fn rgb_writer_task(mut ws2812: PioWs2812<'static, PIO1, 1, { Matrix::NUM_LEDS }>) {
     loop {
         let leds = LEDS.wait().await;
         ws2812.write(&leds).await;
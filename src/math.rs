@@ -6,7 +6,7 @@ pub struct Vec2 {
 }
 
 impl Vec2 {
-    pub fn new(x: i32, y: i32) -> Vec2 {
+    pub const fn new(x: i32, y: i32) -> Vec2 {
         Vec2 { x, y }
     }
     pub fn manhattan(&self) -> i32 {
@@ -1,116 +1,456 @@
-use core::ops::Range;
+use core::{
+    ops::Range,
+    sync::atomic::{AtomicU8, Ordering},
+};
 
 use smart_leds::RGB8;
 
 use crate::{
     color::HV8,
+    hex::{Cube, Neighbors},
+    layouts,
     math::{self, Vec2},
 };
 
-pub const NUM_LEDS: usize = 291;
-pub const MATRIX_MAP: [i16; 21 * 19] = [
-    291, 291, 291, 291, 291, 291, 291, 291, 0, 1, 2, 3, 4, 5, 6, 7, 291, 291, 291, 291, 291, 291,
-    291, 291, 291, 291, 16, 15, 14, 13, 12, 11, 10, 9, 8, 291, 291, 291, 291, 291, 291, 291, 291,
-    291, 291, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 291, 291, 291, 291, 291, 291, 291, 291, 37,
-    36, 35, 34, 33, 32, 31, 30, 29, 28, 27, 291, 291, 291, 291, 291, 38, 39, 40, 41, 42, 43, 44,
-    45, 46, 47, 48, 49, 50, 51, 52, 291, 291, 291, 68, 67, 66, 65, 64, 63, 62, 61, 60, 59, 58, 57,
-    56, 55, 54, 53, 291, 291, 291, 69, 70, 71, 72, 73, 74, 75, 76, 77, 78, 79, 80, 81, 82, 83, 84,
-    85, 291, 102, 101, 100, 99, 98, 97, 96, 95, 94, 93, 92, 91, 90, 89, 88, 87, 86, 291, 291, 103,
-    104, 105, 106, 107, 108, 109, 110, 111, 112, 113, 114, 115, 116, 117, 118, 119, 291, 136, 135,
-    134, 133, 132, 131, 130, 129, 128, 127, 126, 125, 124, 123, 122, 121, 120, 291, 291, 137, 138,
-    139, 140, 141, 142, 143, 144, 145, 146, 147, 148, 149, 150, 151, 152, 153, 291, 291, 170, 169,
-    168, 167, 166, 165, 164, 163, 162, 161, 160, 159, 158, 157, 156, 155, 154, 291, 291, 291, 171,
-    172, 173, 174, 175, 176, 177, 178, 179, 180, 181, 182, 183, 184, 185, 186, 187, 291, 291, 204,
-    203, 202, 201, 200, 199, 198, 197, 196, 195, 194, 193, 192, 191, 190, 189, 188, 291, 291, 291,
-    205, 206, 207, 208, 209, 210, 211, 212, 213, 214, 215, 216, 217, 218, 219, 220, 221, 291, 291,
-    237, 236, 235, 234, 233, 232, 231, 230, 229, 228, 227, 226, 225, 224, 223, 222, 291, 291, 291,
-    291, 238, 239, 240, 241, 242, 243, 244, 245, 246, 247, 248, 249, 250, 251, 252, 291, 291, 291,
-    291, 291, 291, 291, 263, 262, 261, 260, 259, 258, 257, 256, 255, 254, 253, 291, 291, 291, 291,
-    291, 291, 291, 291, 291, 264, 265, 266, 267, 268, 269, 270, 271, 272, 273, 291, 291, 291, 291,
-    291, 291, 291, 291, 291, 282, 281, 280, 279, 278, 277, 276, 275, 274, 291, 291, 291, 291, 291,
-    291, 291, 291, 291, 291, 291, 283, 284, 285, 286, 287, 288, 289, 290, 291, 291, 291,
-];
-pub const MATRIX_WIDTH: usize = 19;
-pub const MATRIX_HEIGHT: usize = 21;
-
-pub const MATRIX_X: Range<usize> = 0..MATRIX_WIDTH;
-pub const MATRIX_Y: Range<usize> = 0..MATRIX_HEIGHT;
+// A physical led matrix. Leds sit on a hex grid in odd-r layout (odd rows shifted right by
+// half a cell), addressed by matrix coordinates 0..WIDTH x 0..HEIGHT.
+pub trait Shape {
+    const WIDTH: usize;
+    const HEIGHT: usize;
+    // matrix cell of odd-r / cube coordinate (0, 0). Must be on an even row, otherwise the
+    // odd-r row parity would flip.
+    const ORIGIN: (usize, usize);
+    // led index for every matrix cell (row major), NUM_LEDS for cells without led
+    const MAP: &'static [i16];
+    // inverse of MAP: matrix (x, y) of each led
+    const LED_XY: &'static [(u8, u8)];
+    // led index ranges fed by the individual power supply lines
+    const ZONES: &'static [Range<usize>];
+    const NUM_LEDS: usize = Self::LED_XY.len();
+
+    fn led_addr(x: usize, y: usize) -> Result<usize, Error> {
+        if x >= Self::WIDTH || y >= Self::HEIGHT {
+            return Err(Error::OutOfBounds);
+        }
+        let led = Self::MAP[x + y * Self::WIDTH];
+        if led >= 0 && (led as usize) < Self::NUM_LEDS {
+            Ok(led as usize)
+        } else {
+            Err(Error::OutOfBounds)
+        }
+    }
+
+    // neighbors of cell v, in CUBE_DIRECTIONS order
+    fn adjacent(v: Vec2) -> [Vec2; 6] {
+        v.neighbors()
+    }
+}
+
+#[cfg(not(any(
+    feature = "layout-hexagon",
+    feature = "layout-rectangle",
+    feature = "layout-ring"
+)))]
+compile_error!("no matrix layout selected, enable one of the layout-* features");
+#[cfg(any(
+    all(feature = "layout-hexagon", feature = "layout-rectangle"),
+    all(feature = "layout-hexagon", feature = "layout-ring"),
+    all(feature = "layout-rectangle", feature = "layout-ring"),
+))]
+compile_error!("more than one matrix layout selected");
+
+#[cfg(feature = "layout-hexagon")]
+pub type Matrix = layouts::Hexagon;
+#[cfg(feature = "layout-rectangle")]
+pub type Matrix = layouts::Rectangle;
+#[cfg(feature = "layout-ring")]
+pub type Matrix = layouts::Ring;
+
+#[derive(Clone, Copy)]
+pub struct Row {
+    // column of the leftmost led
+    pub offset: usize,
+    pub len: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+pub const fn row(offset: usize, len: usize) -> Row {
+    Row { offset, len }
+}
+
+// Row based description of a led matrix, from which the Shape tables (MAP, LED_XY, ZONES)
+// are derived at compile time (see layouts.rs).
+pub struct Layout {
+    pub width: usize,
+    // leds per row, top to bottom
+    pub rows: &'static [Row],
+    // led strip changes direction on every row
+    pub serpentine: bool,
+    // position of the first led, the strip runs horizontally away from it
+    pub start: Corner,
+    // matrix cell for odd-r / cube coordinate (0, 0). Must be on an even row, otherwise
+    // the odd-r row parity would flip.
+    pub origin: (usize, usize),
+    // number of rows (in wiring order) fed by each power zone
+    pub zone_rows: &'static [usize],
+}
+
+impl Layout {
+    pub const fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub const fn num_leds(&self) -> usize {
+        let mut n = 0;
+        let mut y = 0;
+        while y < self.rows.len() {
+            n += self.rows[y].len;
+            y += 1;
+        }
+        n
+    }
+
+    // matrix row of the i-th row in wiring order
+    const fn wired_row(&self, i: usize) -> usize {
+        match self.start {
+            Corner::TopLeft | Corner::TopRight => i,
+            Corner::BottomLeft | Corner::BottomRight => self.height() - 1 - i,
+        }
+    }
+
+    // direction of the i-th row in wiring order
+    const fn left_to_right(&self, i: usize) -> bool {
+        let first = matches!(self.start, Corner::TopLeft | Corner::BottomLeft);
+        if self.serpentine && i % 2 == 1 {
+            !first
+        } else {
+            first
+        }
+    }
+
+    // matrix coordinates of every led, in led order
+    pub const fn led_xy<const N: usize>(&self) -> [(u8, u8); N] {
+        assert!(N == self.num_leds());
+        let mut xy = [(0, 0); N];
+        let mut led = 0;
+        let mut i = 0;
+        while i < self.height() {
+            let y = self.wired_row(i);
+            let row = self.rows[y];
+            assert!(row.offset + row.len <= self.width);
+            let mut j = 0;
+            while j < row.len {
+                let x = if self.left_to_right(i) {
+                    row.offset + j
+                } else {
+                    row.offset + row.len - 1 - j
+                };
+                xy[led] = (x as u8, y as u8);
+                led += 1;
+                j += 1;
+            }
+            i += 1;
+        }
+        xy
+    }
+
+    // led index ranges of the power zones
+    pub const fn zones<const Z: usize>(&self) -> [Range<usize>; Z] {
+        assert!(Z == self.zone_rows.len());
+        let mut zones = [const { 0..0 }; Z];
+        let mut i = 0;
+        let mut start = 0;
+        let mut end = 0;
+        let mut zone = 0;
+        while zone < Z {
+            let mut n = 0;
+            while n < self.zone_rows[zone] {
+                end += self.rows[self.wired_row(i)].len;
+                i += 1;
+                n += 1;
+            }
+            zones[zone] = start..end;
+            start = end;
+            zone += 1;
+        }
+        assert!(i == self.height());
+        zones
+    }
+}
+
+// MAP for a matrix of the given width from its LED_XY table
+pub const fn map_from_xy<const N: usize>(width: usize, xy: &[(u8, u8)]) -> [i16; N] {
+    let mut map = [xy.len() as i16; N];
+    let mut led = 0;
+    while led < xy.len() {
+        let (x, y) = xy[led];
+        map[y as usize * width + x as usize] = led as i16;
+        led += 1;
+    }
+    map
+}
+
+// odd-r coordinates (relative to origin) of every led, in led order
+const fn led_coords<const N: usize>(xy: &[(u8, u8)], origin: (usize, usize)) -> [Vec2; N] {
+    let mut coords = [Vec2::new(0, 0); N];
+    let mut led = 0;
+    while led < N {
+        coords[led] = Vec2::new(
+            xy[led].0 as i32 - origin.0 as i32,
+            xy[led].1 as i32 - origin.1 as i32,
+        );
+        led += 1;
+    }
+    coords
+}
+
+const fn led_cubes<const N: usize>(coords: &[Vec2; N]) -> [Cube; N] {
+    let mut cubes = [Cube::new(0, 0, 0); N];
+    let mut led = 0;
+    while led < N {
+        cubes[led] = Cube::from_oddr(coords[led]);
+        led += 1;
+    }
+    cubes
+}
+
+// shorthands for the selected layout
+pub const MATRIX_MAP: &[i16] = Matrix::MAP;
+pub const LED_XY: &[(u8, u8)] = Matrix::LED_XY;
+// LED_XY in odd-r / cube coordinates, as used by set_matrix_oddr. These are physical
+// positions, before the display transform.
+pub const LED_COORDS: [Vec2; Matrix::NUM_LEDS] = led_coords(LED_XY, Matrix::ORIGIN);
+pub const LED_CUBES: [Cube; Matrix::NUM_LEDS] = led_cubes(&LED_COORDS);
+
+// (led index, matrix x, matrix y) of all leds, in logical coordinates (see Transform). Leds
+// which the transform moves out of the matrix rectangle are skipped.
+pub fn leds_xy() -> impl Iterator<Item = (usize, usize, usize)> {
+    leds_oddr().filter_map(|(i, v)| {
+        let (x, y) = oddr_to_matrix(v);
+        (x < Matrix::WIDTH && y < Matrix::HEIGHT).then_some((i, x, y))
+    })
+}
+
+// (led index, odd-r coordinate) of all leds, in logical coordinates (see Transform)
+pub fn leds_oddr() -> impl Iterator<Item = (usize, Vec2)> {
+    leds_cube().map(|(i, c)| (i, c.to_oddr()))
+}
+
+// (led index, cube coordinate) of all leds, in logical coordinates (see Transform)
+pub fn leds_cube() -> impl Iterator<Item = (usize, Cube)> {
+    let t = transform();
+    LED_CUBES
+        .iter()
+        .enumerate()
+        .map(move |(i, c)| (i, t.invert(*c)))
+}
+
+// Orientation of the panel: maps the logical coordinates used by apps (odd-r / cube) to
+// physical ones, by mirroring left / right first and then rotating around the origin.
+// Matrix (x, y) coordinates (set_matrix, led_addr, leds_xy) are logical too: cell (x, y) is
+// odd-r coordinate (x, y) - ORIGIN. Only the tables (MATRIX_MAP, LED_XY, ...) are physical.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct Transform {
+    // 60° steps clockwise, 0..6
+    pub rotation: u8,
+    pub mirror: bool,
+}
+
+impl Transform {
+    pub fn apply(self, c: Cube) -> Cube {
+        let c = if self.mirror { c.mirror() } else { c };
+        c.rotate(self.rotation as i32)
+    }
+    // physical to logical coordinates
+    pub fn invert(self, c: Cube) -> Cube {
+        let c = c.rotate(-(self.rotation as i32));
+        if self.mirror {
+            c.mirror()
+        } else {
+            c
+        }
+    }
+}
+
+// rotation in bits 0..3, mirror in bit 3
+static TRANSFORM: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_transform(t: Transform) {
+    TRANSFORM.store(
+        (t.rotation % 6) | ((t.mirror as u8) << 3),
+        Ordering::Relaxed,
+    );
+}
+
+pub fn transform() -> Transform {
+    let t = TRANSFORM.load(Ordering::Relaxed);
+    Transform {
+        rotation: t & 0x7,
+        mirror: t & 0x8 != 0,
+    }
+}
+
+pub const MATRIX_X: Range<usize> = 0..Matrix::WIDTH;
+pub const MATRIX_Y: Range<usize> = 0..Matrix::HEIGHT;
 
 #[derive(Debug)]
 pub enum Error {
     OutOfBounds,
 }
 
-pub fn set_matrix_oddr(v: math::Vec2, color: RGB8, data: &mut [RGB8; NUM_LEDS]) {
-    let _ = set_matrix((v.x + 10) as usize, (v.y + 10) as usize, color, data);
+pub fn set_matrix_oddr(v: math::Vec2, color: RGB8, data: &mut [RGB8; Matrix::NUM_LEDS]) {
+    if let Ok(led) = led_addr_oddr(v) {
+        data[led] = color;
+    }
+}
+
+// matrix coordinates of odd-r coordinate v (out of range values wrap and are rejected later)
+pub fn oddr_to_matrix(v: math::Vec2) -> (usize, usize) {
+    (
+        (v.x + Matrix::ORIGIN.0 as i32) as usize,
+        (v.y + Matrix::ORIGIN.1 as i32) as usize,
+    )
 }
 
 pub fn set_matrix(
     x: usize,
     y: usize,
     color: RGB8,
-    data: &mut [RGB8; NUM_LEDS],
+    data: &mut [RGB8; Matrix::NUM_LEDS],
 ) -> Result<i16, Error> {
-    if x >= MATRIX_WIDTH || y >= MATRIX_HEIGHT {
-        return Err(Error::OutOfBounds);
-    }
-    let addr = x + y * MATRIX_WIDTH;
-    let led = MATRIX_MAP.get(addr).ok_or(Error::OutOfBounds)?;
-    let rgb = data.get_mut(*led as usize).ok_or(Error::OutOfBounds)?;
-    *rgb = color;
-    Ok(*led)
+    let led = led_addr(x, y)?;
+    data[led] = color;
+    Ok(led as i16)
 }
 
-pub fn get_matrix(x: usize, y: usize, data: &mut [RGB8; NUM_LEDS]) -> Result<(i16, RGB8), Error> {
-    if x >= MATRIX_WIDTH || y >= MATRIX_HEIGHT {
-        return Err(Error::OutOfBounds);
-    }
-    let addr = x + y * MATRIX_WIDTH;
-    let led = MATRIX_MAP.get(addr).ok_or(Error::OutOfBounds)?;
-    Ok((
-        *led,
-        data.get(*led as usize).cloned().ok_or(Error::OutOfBounds)?,
-    ))
+pub fn get_matrix(
+    x: usize,
+    y: usize,
+    data: &mut [RGB8; Matrix::NUM_LEDS],
+) -> Result<(i16, RGB8), Error> {
+    let led = led_addr(x, y)?;
+    Ok((led as i16, data[led]))
 }
 pub fn set_matrix_hv(
     x: usize,
     y: usize,
     hv: &HV8,
-    data: &mut [HV8; NUM_LEDS],
+    data: &mut [HV8; Matrix::NUM_LEDS],
 ) -> Result<i16, Error> {
-    if x >= MATRIX_WIDTH || y >= MATRIX_HEIGHT {
-        return Err(Error::OutOfBounds);
-    }
-    let addr = x + y * MATRIX_WIDTH;
-    let led = MATRIX_MAP.get(addr).ok_or(Error::OutOfBounds)?;
-    let out = data.get_mut(*led as usize).ok_or(Error::OutOfBounds)?;
-    *out = *hv;
-    Ok(*led)
+    let led = led_addr(x, y)?;
+    data[led] = *hv;
+    Ok(led as i16)
 }
 
+// led at logical matrix cell (x, y)
 pub fn led_addr(x: usize, y: usize) -> Result<usize, Error> {
-    if x >= MATRIX_WIDTH || y >= MATRIX_HEIGHT {
-        return Err(Error::OutOfBounds);
-    }
-    let addr = x + y * MATRIX_WIDTH;
-    let led = MATRIX_MAP.get(addr).ok_or(Error::OutOfBounds)?;
-    if *led >= 0 && (*led as usize) < NUM_LEDS {
-        Ok(*led as usize)
-    } else {
-        Err(Error::OutOfBounds)
-    }
-}
-pub fn adjacent(v: Vec2) -> [Vec2; 6] {
-    let xshift = v.y.abs() % 2;
-    let mut d = [
-        Vec2::new(1, 0),
-        Vec2::new(-1, 0),
-        Vec2::new(-1 + xshift, 1),
-        Vec2::new(xshift, 1),
-        Vec2::new(-1 + xshift, -1),
-        Vec2::new(xshift, -1),
-    ];
-
-    d.iter_mut().for_each(|f| *f += v);
-    d
+    if transform() == Transform::default() {
+        return Matrix::led_addr(x, y);
+    }
+    led_addr_oddr(math::Vec2::new(
+        x as i32 - Matrix::ORIGIN.0 as i32,
+        y as i32 - Matrix::ORIGIN.1 as i32,
+    ))
+}
+// led at logical odd-r coordinate v
+pub fn led_addr_oddr(v: math::Vec2) -> Result<usize, Error> {
+    let (x, y) = oddr_to_matrix(transform().apply(Cube::from_oddr(v)).to_oddr());
+    Matrix::led_addr(x, y)
+}
+
+// tests drawing to the matrix depend on the global transform
+#[cfg(test)]
+pub(crate) fn lock_transform() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+
+    // physical led at odd-r coordinate (x, y)
+    fn physical(x: i32, y: i32) -> usize {
+        let (x, y) = oddr_to_matrix(math::Vec2::new(x, y));
+        Matrix::led_addr(x, y).unwrap()
+    }
+
+    #[test]
+    fn set_matrix_follows_transform() {
+        let _lock = lock_transform();
+        // k cells east of the origin, far enough out to miss a hole in the middle
+        let has_led = |x, y| Matrix::led_addr(x, y).is_ok();
+        let k = (2..)
+            .step_by(2)
+            .find(|k| {
+                let (x, y) = oddr_to_matrix(math::Vec2::new(*k, 0));
+                has_led(x, y)
+            })
+            .unwrap();
+        let (x, y) = oddr_to_matrix(math::Vec2::new(k, 0));
+        for (t, led) in [
+            (Transform::default(), physical(k, 0)),
+            // k cells east turned 60° clockwise: k cells south east
+            (
+                Transform {
+                    rotation: 1,
+                    mirror: false,
+                },
+                physical(k / 2, k),
+            ),
+            (
+                Transform {
+                    rotation: 3,
+                    mirror: false,
+                },
+                physical(-k, 0),
+            ),
+            (
+                Transform {
+                    rotation: 0,
+                    mirror: true,
+                },
+                physical(-k, 0),
+            ),
+        ] {
+            set_transform(t);
+            let mut data = [color::BLACK; Matrix::NUM_LEDS];
+            assert_eq!(
+                set_matrix(x, y, color::WHITE, &mut data).unwrap(),
+                led as i16
+            );
+            assert_eq!(data[led], color::WHITE);
+            assert_eq!(data.iter().filter(|c| **c != color::BLACK).count(), 1);
+            assert_eq!(
+                get_matrix(x, y, &mut data).unwrap(),
+                (led as i16, color::WHITE)
+            );
+        }
+        set_transform(Transform::default());
+    }
+
+    #[test]
+    fn leds_xy_matches_led_addr() {
+        let _lock = lock_transform();
+        for rotation in 0..6 {
+            for mirror in [false, true] {
+                set_transform(Transform { rotation, mirror });
+                for (i, x, y) in leds_xy() {
+                    assert_eq!(led_addr(x, y).unwrap(), i);
+                }
+                for (i, v) in leds_oddr() {
+                    assert_eq!(led_addr_oddr(v).unwrap(), i);
+                }
+            }
+        }
+        set_transform(Transform::default());
+    }
 }
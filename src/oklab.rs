@@ -0,0 +1,224 @@
+use smart_leds::RGB8;
+
+use crate::color::RGB16;
+
+// OKLab perceptual color space (https://bottosson.github.io/posts/oklab/) in fixed point,
+// for blending without the muddy / too dark midpoints of lerping RGB or the hue sweeps of
+// lerping HSV. RGB8 values are taken as linear light, which is what apps render (gamma is
+// applied by the output stage).
+//
+// Integer only: the cube root is computed bit by bit with shifts and adds, the matrices use
+// 12 / 16 bit fixed point coefficients. Colors roundtrip through Lab within 1 per channel.
+
+// fixed point 1.0 of Lab
+pub const ONE: i32 = 1 << 12;
+
+// l in 0..=ONE (black to white), a and b roughly -ONE / 2..ONE / 2
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Lab {
+    pub l: i32,
+    pub a: i32,
+    pub b: i32,
+}
+
+impl Lab {
+    // t = 0 is self, 255 is other
+    pub fn lerp(&self, other: &Lab, t: u8) -> Lab {
+        let f = |a: i32, b: i32| a + (b - a) * t as i32 / 255;
+        Lab {
+            l: f(self.l, other.l),
+            a: f(self.a, other.a),
+            b: f(self.b, other.b),
+        }
+    }
+}
+
+// t = 0 is a, 255 is b
+pub fn lerp(a: RGB8, b: RGB8, t: u8) -> RGB8 {
+    match t {
+        0 => a,
+        255 => b,
+        _ => Lab::from(a).lerp(&Lab::from(b), t).into(),
+    }
+}
+
+// lerp every led, for crossfading between whole frames
+pub fn lerp_all(out: &mut [RGB8], from: &[RGB8], to: &[RGB8], t: u8) {
+    for (o, (a, b)) in out.iter_mut().zip(from.iter().zip(to)) {
+        *o = lerp(*a, *b, t);
+    }
+}
+
+// sum of c[i] * v[i] with coefficients scaled by 1 << 12 or 1 << 16
+fn dot(c: &[i32; 3], v: [i32; 3]) -> i32 {
+    c[0] * v[0] + c[1] * v[1] + c[2] * v[2]
+}
+
+// linear RGB to LMS, 1 << 16
+const M1: [[i32; 3]; 3] = [
+    [27015, 35149, 3372],
+    [13887, 44610, 7038],
+    [5787, 18463, 41286],
+];
+// cube root of LMS to Lab, 1 << 12
+const M2: [[i32; 3]; 3] = [[862, 3251, -17], [8102, -9948, 1846], [106, 3206, -3312]];
+// Lab to cube root of LMS, 1 << 12
+const M2_INV: [[i32; 3]; 3] = [[4096, 1623, 884], [4096, -432, -262], [4096, -367, -5290]];
+// LMS to linear RGB, 1 << 12
+const M1_INV: [[i32; 3]; 3] = [
+    [16698, -13548, 946],
+    [-5196, 10690, -1398],
+    [-17, -2881, 6994],
+];
+
+impl From<RGB8> for Lab {
+    fn from(c: RGB8) -> Self {
+        let rgb = [c.r as i32, c.g as i32, c.b as i32];
+        // LMS in 0..=1 << 16, then its cube root in 0..=1 << 12 (rounded from 1 << 13)
+        let lms = M1.map(|row| {
+            let v = (dot(&row, rgb) + 127) / 255;
+            (cbrt((v.max(0) as u64) << 23) as i32 + 1) >> 1
+        });
+        // 1 << 12 times 1 << 12, down to 1 << 12
+        let [l, a, b] = M2.map(|row| (dot(&row, lms) + (1 << 11)) >> 12);
+        Lab { l, a, b }
+    }
+}
+
+// linear RGB in 1 << 16, channels outside of the RGB gamut are clamped to 0 (the caller
+// clamps the upper end)
+fn to_linear(c: Lab) -> [i32; 3] {
+    let lab = [c.l, c.a, c.b];
+    // 1 << 12, colors in gamut are within 0..=ONE, the limit keeps M1_INV from overflowing
+    let lms = M2_INV.map(|row| {
+        let v = ((dot(&row, lab) + (1 << 11)) >> 12).clamp(-5 * ONE / 4, 5 * ONE / 4);
+        // 1 << 24 down to 1 << 14
+        (((v * v) >> 12) * v) >> 10
+    });
+    // 1 << 26, down to 1 << 16
+    M1_INV.map(|row| dot(&row, lms).max(0) >> 10)
+}
+
+impl From<Lab> for RGB8 {
+    fn from(c: Lab) -> Self {
+        let [r, g, b] = to_linear(c).map(|v| ((v * 255 + (1 << 15)) >> 16).min(255) as u8);
+        RGB8::new(r, g, b)
+    }
+}
+
+// same as RGB8, with the precision for smooth fades at low brightness
+impl From<Lab> for RGB16 {
+    fn from(c: Lab) -> Self {
+        let [r, g, b] =
+            to_linear(c).map(|v| ((v as i64 * 65535 + (1 << 15)) >> 16).min(65535) as u16);
+        RGB16::new(r, g, b)
+    }
+}
+
+// floor of the cube root, bit by bit (Hacker's Delight, icbrt)
+fn cbrt(mut x: u64) -> u32 {
+    let mut y: u32 = 0;
+    for s in (0..=63).rev().step_by(3) {
+        y *= 2;
+        // x >= b << s without overflowing b << s
+        let b = 3 * y as u64 * (y as u64 + 1) + 1;
+        if x >> s >= b {
+            x -= b << s;
+            y += 1;
+        }
+    }
+    y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color;
+    use std::collections::BTreeSet;
+
+    fn assert_close(c: RGB8, l: f32, a: f32, b: f32) {
+        let lab = Lab::from(c);
+        let f = |v: i32| v as f32 / ONE as f32;
+        let ok = |x: f32, y: f32| (x - y).abs() < 0.002;
+        assert!(
+            ok(f(lab.l), l) && ok(f(lab.a), a) && ok(f(lab.b), b),
+            "{:?}: {:?} expected ({}, {}, {})",
+            c,
+            lab,
+            l,
+            a,
+            b
+        );
+    }
+
+    // from the float reference implementation, fed with linear values (c / 255)
+    #[test]
+    fn reference_values() {
+        assert_close(RGB8::new(0, 0, 0), 0.0, 0.0, 0.0);
+        assert_close(RGB8::new(255, 255, 255), 1.0, 0.0, 0.0);
+        assert_close(RGB8::new(255, 0, 0), 0.627955, 0.224863, 0.125846);
+        assert_close(RGB8::new(0, 255, 0), 0.866440, -0.233888, 0.179498);
+        assert_close(RGB8::new(0, 0, 255), 0.452014, -0.032457, -0.311528);
+        assert_close(RGB8::new(255, 255, 0), 0.967983, -0.071369, 0.198570);
+        assert_close(RGB8::new(128, 128, 128), 0.794737, 0.0, 0.0);
+        assert_close(RGB8::new(10, 40, 200), 0.569710, -0.022657, -0.188519);
+    }
+
+    #[test]
+    fn roundtrip() {
+        for r in (0..=255).step_by(15) {
+            for g in (0..=255).step_by(15) {
+                for b in (0..=255).step_by(15) {
+                    let c = RGB8::new(r, g, b);
+                    let back = RGB8::from(Lab::from(c));
+                    let d = |x: u8, y: u8| (x as i32 - y as i32).abs();
+                    assert!(
+                        d(c.r, back.r) <= 1 && d(c.g, back.g) <= 1 && d(c.b, back.b) <= 1,
+                        "{:?} -> {:?}",
+                        c,
+                        back
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn blending() {
+        let (red, blue) = (RGB8::new(255, 0, 0), RGB8::new(0, 0, 255));
+        assert_eq!(lerp(red, blue, 0), red);
+        assert_eq!(lerp(red, blue, 255), blue);
+        // perceptual middle gray: L = 0.5 is 1 / 8 of the light, not the 1 / 2 of RGB
+        let gray = lerp(RGB8::new(0, 0, 0), RGB8::new(255, 255, 255), 128);
+        assert!((31..=33).contains(&gray.r) && gray.r == gray.g && gray.g == gray.b);
+        // lightness changes evenly along the way
+        let (l_red, l_blue) = (Lab::from(red).l, Lab::from(blue).l);
+        let mid = Lab::from(lerp(red, blue, 128)).l;
+        assert!((mid - (l_red + l_blue) / 2).abs() < 8, "{}", mid);
+    }
+
+    #[test]
+    fn rgb16() {
+        let rgb16 = |c: RGB8| RGB16::from(Lab::from(c));
+        assert_eq!(rgb16(RGB8::new(0, 0, 0)), RGB16::new(0, 0, 0));
+        assert_eq!(
+            rgb16(RGB8::new(255, 255, 255)),
+            RGB16::new(65535, 65535, 65535)
+        );
+        for c in [(255, 0, 0), (10, 40, 200), (1, 2, 3), (128, 128, 128)] {
+            let c = RGB8::new(c.0, c.1, c.2);
+            let lab = Lab::from(c);
+            assert_eq!(color::to_rgb8(RGB16::from(lab)), RGB8::from(lab));
+        }
+        // a slow fade between two dark colors has more distinct steps in 16 bit
+        let (a, b) = (Lab::from(RGB8::new(0, 0, 2)), Lab::from(RGB8::new(0, 0, 6)));
+        let steps16 = (0..=255)
+            .map(|t| RGB16::from(a.lerp(&b, t)).b)
+            .collect::<BTreeSet<_>>();
+        let steps8 = (0..=255)
+            .map(|t| RGB8::from(a.lerp(&b, t)).b)
+            .collect::<BTreeSet<_>>();
+        assert_eq!(steps8.len(), 5);
+        assert!(steps16.len() > 50, "{}", steps16.len());
+    }
+}
@@ -0,0 +1,304 @@
+// powf, inherent when the tests link std
+#[cfg(not(test))]
+use num_traits::Float;
+use smart_leds::RGB8;
+
+use crate::color::RGB16;
+
+// Last stage before the leds. Apps render linear light values, this maps them to what the
+// WS2812 should be sent so all apps look alike: per channel gamma, white balance (the leds'
+// own white is bluish), color temperature and a global brightness. Apps render 16 bit
+// values (see App::tick16), the gamma curves are tables interpolated between their 256
+// entries, followed by one multiplication per channel for everything else. The result is
+// brought down to 8 bit by Dither.
+
+// led corrections, scale per channel to get a neutral white at full power
+pub const UNCORRECTED: RGB8 = RGB8 {
+    r: 255,
+    g: 255,
+    b: 255,
+};
+pub const TYPICAL_SMD5050: RGB8 = RGB8 {
+    r: 255,
+    g: 176,
+    b: 240,
+};
+
+// color temperatures of the white point
+pub const CANDLE: RGB8 = RGB8 {
+    r: 255,
+    g: 147,
+    b: 41,
+};
+pub const TUNGSTEN_100W: RGB8 = RGB8 {
+    r: 255,
+    g: 214,
+    b: 170,
+};
+pub const HALOGEN: RGB8 = RGB8 {
+    r: 255,
+    g: 241,
+    b: 224,
+};
+pub const DIRECT_SUNLIGHT: RGB8 = UNCORRECTED;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    // exponent per channel (r, g, b), 1.0 is linear
+    pub gamma: [f32; 3],
+    pub correction: RGB8,
+    pub temperature: RGB8,
+    pub brightness: u8,
+}
+
+impl Config {
+    // the curve HV8 used to apply on its own (gamma 2.8), no color correction
+    pub const DEFAULT: Config = Config {
+        gamma: [2.8; 3],
+        correction: UNCORRECTED,
+        temperature: DIRECT_SUNLIGHT,
+        brightness: 255,
+    };
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::DEFAULT
+    }
+}
+
+pub struct Output {
+    config: Config,
+    // gamma curves with 16 bit precision, only recomputed when gamma changes
+    curve: [[u16; 256]; 3],
+    // correction, temperature and brightness combined, 65536 is 1.0
+    scale: [u32; 3],
+}
+
+impl Output {
+    pub fn new(config: Config) -> Output {
+        let mut output = Output {
+            config,
+            curve: [[0; 256]; 3],
+            scale: [0; 3],
+        };
+        output.update_curve();
+        output.update_scale();
+        output
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: Config) {
+        let gamma_changed = config.gamma != self.config.gamma;
+        self.config = config;
+        if gamma_changed {
+            self.update_curve();
+        }
+        self.update_scale();
+    }
+
+    // cheap, can be called every frame
+    pub fn set_brightness(&mut self, brightness: u8) {
+        if brightness != self.config.brightness {
+            self.config.brightness = brightness;
+            self.update_scale();
+        }
+    }
+
+    pub fn set_temperature(&mut self, temperature: RGB8) {
+        if temperature != self.config.temperature {
+            self.config.temperature = temperature;
+            self.update_scale();
+        }
+    }
+
+    fn update_curve(&mut self) {
+        for (curve, gamma) in self.curve.iter_mut().zip(self.config.gamma) {
+            for (i, out) in curve.iter_mut().enumerate() {
+                *out = ((i as f32 / 255.0).powf(gamma) * 65535.0 + 0.5) as u16;
+            }
+        }
+    }
+
+    fn update_scale(&mut self) {
+        let Config {
+            correction: c,
+            temperature: t,
+            brightness,
+            ..
+        } = self.config;
+        let scale = [
+            c.r as u64 * t.r as u64,
+            c.g as u64 * t.g as u64,
+            c.b as u64 * t.b as u64,
+        ];
+        for (out, scale) in self.scale.iter_mut().zip(scale) {
+            // correction / 255 * temperature / 255 * brightness / 255
+            *out = (scale * brightness as u64 * 65536 / (255 * 255 * 255)) as u32;
+        }
+    }
+
+    pub fn apply16(&self, c: RGB16) -> RGB16 {
+        let channel = |k: usize, v: u16| {
+            // position on the curve in 8.8 fixed point, v = x * 257 lands on entry x
+            let p = v as u32 * 256 / 257;
+            let (i, f) = (p as usize >> 8, p & 0xff);
+            let curve = &self.curve[k];
+            let (a, b) = (curve[i] as u32, curve[(i + 1).min(255)] as u32);
+            let v = a + (((b - a) * f) >> 8);
+            ((v * self.scale[k]) >> 16) as u16
+        };
+        RGB16::new(channel(0, c.r), channel(1, c.g), channel(2, c.b))
+    }
+
+    pub fn apply16_all(&self, leds: &mut [RGB16]) {
+        leds.iter_mut().for_each(|c| *c = self.apply16(*c));
+    }
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output::new(Config::DEFAULT)
+    }
+}
+
+// Temporal error diffusion from 16 to 8 bit: what gets cut off when an led is brought down
+// to 8 bit is carried over to the same led in the next frame. Over a few frames the led
+// averages out to the 16 bit value, so fades no longer step visibly at low brightness.
+pub struct Dither<const N: usize> {
+    error: [[u8; 3]; N],
+}
+
+impl<const N: usize> Dither<N> {
+    pub const fn new() -> Self {
+        Dither { error: [[0; 3]; N] }
+    }
+
+    pub fn apply(&mut self, input: &[RGB16; N], out: &mut [RGB8; N]) {
+        for ((c, o), e) in input.iter().zip(out.iter_mut()).zip(self.error.iter_mut()) {
+            *o = RGB8::new(
+                dither(c.r, &mut e[0]),
+                dither(c.g, &mut e[1]),
+                dither(c.b, &mut e[2]),
+            );
+        }
+    }
+}
+
+impl<const N: usize> Default for Dither<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn dither(v: u16, error: &mut u8) -> u8 {
+    // 8.8 fixed point, 65535 is 255.0
+    let acc = v as u32 * 256 / 257 + *error as u32;
+    *error = acc as u8;
+    (acc >> 8) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(v: u16) -> RGB16 {
+        RGB16::new(v, v, v)
+    }
+
+    #[test]
+    fn linear_identity() {
+        let output = Output::new(Config {
+            gamma: [1.0; 3],
+            ..Config::DEFAULT
+        });
+        for x in 0..=255u16 {
+            assert_eq!(output.apply16(gray(x * 257)), gray(x * 257));
+        }
+    }
+
+    #[test]
+    fn corrections() {
+        let mut output = Output::default();
+        assert_eq!(output.apply16(gray(0)), gray(0));
+        assert_eq!(output.apply16(gray(65535)), gray(65535));
+        // gamma 2.8: half input is a lot less than half output
+        let half = output.apply16(gray(32768)).r;
+        assert!((9000..10000).contains(&half), "{}", half);
+
+        // the combined scale is truncated, off by at most one
+        output.set_brightness(128);
+        assert!((output.apply16(gray(65535)).r as i32 - 65535 * 128 / 255).abs() <= 1);
+        output.set_config(Config {
+            correction: TYPICAL_SMD5050,
+            temperature: CANDLE,
+            ..Config::DEFAULT
+        });
+        let white = output.apply16(gray(65535));
+        let expected = |c: u8, t: u8| (65535 * c as u32 / 255 * t as u32 / 255) as i32;
+        assert!((white.r as i32 - expected(255, 255)).abs() <= 1);
+        assert!((white.g as i32 - expected(176, 147)).abs() <= 1);
+        assert!((white.b as i32 - expected(240, 41)).abs() <= 1);
+    }
+
+    #[test]
+    fn apply16_full_scale() {
+        // the largest scale and the steepest curve must not overflow
+        for gamma in [0.5, 1.0, 2.8] {
+            let output = Output::new(Config {
+                gamma: [gamma; 3],
+                ..Config::DEFAULT
+            });
+            assert_eq!(output.apply16(gray(0)), gray(0));
+            assert_eq!(output.apply16(gray(65535)), gray(65535));
+            assert_eq!(
+                output.apply16(RGB16::new(65535, 0, 65535)),
+                RGB16::new(65535, 0, 65535)
+            );
+            // monotonic in between
+            let mut last = 0;
+            for v in (0..=65535).step_by(97) {
+                let out = output.apply16(gray(v)).g;
+                assert!(out >= last, "{} {}", gamma, v);
+                last = out;
+            }
+        }
+        let mut output = Output::default();
+        output.set_brightness(0);
+        assert_eq!(output.apply16(gray(65535)), gray(0));
+    }
+
+    #[test]
+    fn dither_averages_to_input() {
+        let values = [0, 1, 128, 257, 1000, 32767, 40000, 65534, 65535];
+        let input = values.map(|v| RGB16::new(v, 65535 - v, v / 2));
+        let mut dither = Dither::<9>::new();
+        let mut out = [RGB8::default(); 9];
+        let mut sum = [[0u32; 3]; 9];
+        for _ in 0..256 {
+            dither.apply(&input, &mut out);
+            for (s, o) in sum.iter_mut().zip(out) {
+                s[0] += o.r as u32;
+                s[1] += o.g as u32;
+                s[2] += o.b as u32;
+            }
+        }
+        for (s, c) in sum.iter().zip(input) {
+            for (s, v) in s.iter().zip([c.r, c.g, c.b]) {
+                // the 8.8 fixed point value exactly, which is the input within 1
+                assert_eq!(*s, v as u32 * 256 / 257);
+                assert!((*s as i32 * 257 / 256 - v as i32).abs() <= 1, "{} {}", s, v);
+            }
+        }
+        // full scale stays at 255 without wrapping around
+        let mut dither = Dither::<1>::new();
+        let mut out = [RGB8::default()];
+        for _ in 0..300 {
+            dither.apply(&[gray(65535)], &mut out);
+            assert_eq!(out[0], RGB8::new(255, 255, 255));
+        }
+    }
+}
@@ -0,0 +1,255 @@
+use smart_leds::RGB8;
+
+use crate::color::{lerp8, BLACK};
+
+// Color lookup for scalar values (heat, density, age, ...). The index range 0..=255 is
+// spread evenly over the N entries and interpolated between them, so a 16 entry palette
+// is as smooth as a 256 entry one for gradients without sharp edges. Palettes are built
+// from gradient stops, at compile time for the built-ins.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette<const N: usize> {
+    pub entries: [RGB8; N],
+}
+
+pub type Palette16 = Palette<16>;
+pub type Palette256 = Palette<256>;
+
+// position 0..=255 along the palette and its color
+pub type Stop = (u8, RGB8);
+
+const fn rgb(r: u8, g: u8, b: u8) -> RGB8 {
+    RGB8 { r, g, b }
+}
+
+pub const FIRE: Palette16 = Palette::from_gradient(&[
+    (0, BLACK),
+    (80, rgb(120, 10, 0)),
+    (150, rgb(255, 80, 8)),
+    (220, rgb(255, 170, 40)),
+    (255, rgb(255, 240, 150)),
+]);
+pub const OCEAN: Palette16 = Palette::from_gradient(&[
+    (0, rgb(0, 0, 16)),
+    (90, rgb(0, 30, 120)),
+    (170, rgb(0, 120, 180)),
+    (230, rgb(60, 200, 220)),
+    (255, rgb(200, 255, 255)),
+]);
+pub const LAVA: Palette16 = Palette::from_gradient(&[
+    (0, BLACK),
+    (50, rgb(40, 0, 10)),
+    (110, rgb(150, 0, 0)),
+    (170, rgb(255, 40, 0)),
+    (220, rgb(255, 140, 0)),
+    (255, rgb(255, 255, 255)),
+]);
+pub const FOREST: Palette16 = Palette::from_gradient(&[
+    (0, rgb(0, 20, 0)),
+    (80, rgb(0, 80, 10)),
+    (150, rgb(40, 130, 20)),
+    (210, rgb(110, 160, 40)),
+    (255, rgb(180, 200, 90)),
+]);
+// ends where it starts, for cycling through it
+pub const PARTY: Palette16 = Palette::from_gradient(&[
+    (0, rgb(90, 0, 170)),
+    (50, rgb(200, 0, 90)),
+    (100, rgb(255, 60, 0)),
+    (150, rgb(255, 200, 0)),
+    (200, rgb(0, 120, 255)),
+    (255, rgb(90, 0, 170)),
+]);
+// black body like: black, red, yellow, white
+pub const HEAT: Palette16 = Palette::from_gradient(&[
+    (0, BLACK),
+    (85, rgb(255, 0, 0)),
+    (170, rgb(255, 255, 0)),
+    (255, rgb(255, 255, 255)),
+]);
+
+pub const PALETTES: &[(&str, Palette16)] = &[
+    ("fire", FIRE),
+    ("ocean", OCEAN),
+    ("lava", LAVA),
+    ("forest", FOREST),
+    ("party", PARTY),
+    ("heat", HEAT),
+];
+
+pub fn find(name: &str) -> Option<&'static Palette16> {
+    PALETTES.iter().find(|(n, _)| *n == name).map(|(_, p)| p)
+}
+
+impl<const N: usize> Palette<N> {
+    // Stops sorted by position. Before the first and after the last stop their color is
+    // held.
+    pub const fn from_gradient(stops: &[Stop]) -> Self {
+        assert!(N >= 2 && !stops.is_empty());
+        let mut entries = [BLACK; N];
+        let mut i = 0;
+        while i < N {
+            entries[i] = gradient(stops, (i * 255 / (N - 1)) as u8);
+            i += 1;
+        }
+        Palette { entries }
+    }
+
+    pub fn get(&self, index: u8) -> RGB8 {
+        let pos = index as usize * (N - 1);
+        let (i, f) = (pos / 255, (pos % 255) as u8);
+        if f == 0 {
+            return self.entries[i];
+        }
+        lerp(self.entries[i], self.entries[i + 1], f)
+    }
+
+    // crossfade entry by entry, t = 0 is self, 255 is other
+    pub fn blend(&self, other: &Self, t: u8) -> Self {
+        let mut out = *self;
+        for (o, e) in out.entries.iter_mut().zip(other.entries.iter()) {
+            *o = lerp(*o, *e, t);
+        }
+        out
+    }
+
+    // Move every channel up to step closer to target, for crossfading over several
+    // frames. Returns true once target is reached.
+    pub fn fade_towards(&mut self, target: &Self, step: u8) -> bool {
+        let towards = |a: &mut u8, b: u8| {
+            *a = if *a < b {
+                a.saturating_add(step).min(b)
+            } else {
+                a.saturating_sub(step).max(b)
+            };
+        };
+        for (o, e) in self.entries.iter_mut().zip(target.entries.iter()) {
+            towards(&mut o.r, e.r);
+            towards(&mut o.g, e.g);
+            towards(&mut o.b, e.b);
+        }
+        self == target
+    }
+}
+
+const fn lerp(a: RGB8, b: RGB8, t: u8) -> RGB8 {
+    rgb(lerp8(a.r, b.r, t), lerp8(a.g, b.g, t), lerp8(a.b, b.b, t))
+}
+
+// color of the gradient at pos
+const fn gradient(stops: &[Stop], pos: u8) -> RGB8 {
+    let mut i = 0;
+    while i < stops.len() && stops[i].0 < pos {
+        i += 1;
+    }
+    if i == 0 {
+        return stops[0].1;
+    }
+    if i == stops.len() {
+        return stops[i - 1].1;
+    }
+    let ((p0, c0), (p1, c1)) = (stops[i - 1], stops[i]);
+    let t = (pos - p0) as u32 * 255 / (p1 - p0) as u32;
+    lerp(c0, c1, t as u8)
+}
+
+// Values of a scalar field that can be looked up in a palette, see
+// HexField::to_leds_palette.
+pub trait Level: Copy {
+    // 0..=255
+    fn level(self) -> u8;
+}
+
+impl Level for u8 {
+    fn level(self) -> u8 {
+        self
+    }
+}
+
+// 0.0..=1.0, clamped
+impl Level for f32 {
+    fn level(self) -> u8 {
+        (self.clamp(0.0, 1.0) * 255.0) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::WHITE;
+
+    const GRAY: Palette<2> = Palette::from_gradient(&[(0, BLACK), (255, WHITE)]);
+
+    fn gray(v: u8) -> RGB8 {
+        rgb(v, v, v)
+    }
+
+    #[test]
+    fn from_gradient() {
+        assert_eq!(GRAY.entries, [BLACK, WHITE]);
+        let ramp: Palette256 = Palette::from_gradient(&[(0, BLACK), (255, WHITE)]);
+        for (i, c) in ramp.entries.iter().enumerate() {
+            assert_eq!(*c, gray(i as u8));
+        }
+        // stops at 85 / 170 land exactly on entries 5 / 10
+        assert_eq!(HEAT.entries[0], BLACK);
+        assert_eq!(HEAT.entries[5], rgb(255, 0, 0));
+        assert_eq!(HEAT.entries[10], rgb(255, 255, 0));
+        assert_eq!(HEAT.entries[15], WHITE);
+        assert_eq!(HEAT.entries[3], rgb(153, 0, 0));
+        // first and last stop colors are held towards the ends
+        let held: Palette16 = Palette::from_gradient(&[(100, WHITE), (200, BLACK)]);
+        assert!(held.entries[..6].iter().all(|c| *c == WHITE));
+        assert!(held.entries[12..].iter().all(|c| *c == BLACK));
+    }
+
+    #[test]
+    fn get() {
+        for i in 0..=255 {
+            assert_eq!(GRAY.get(i), gray(i));
+        }
+        for p in PALETTES.iter().map(|(_, p)| p) {
+            assert_eq!(p.get(0), p.entries[0]);
+            assert_eq!(p.get(255), p.entries[15]);
+            assert_eq!(p.get(17), p.entries[1]);
+        }
+        // halfway between two entries
+        assert_eq!(
+            HEAT.get(17 * 5 + 8),
+            lerp(HEAT.entries[5], HEAT.entries[6], 8 * 15)
+        );
+    }
+
+    #[test]
+    fn blend() {
+        assert_eq!(FIRE.blend(&OCEAN, 0), FIRE);
+        assert_eq!(FIRE.blend(&OCEAN, 255), OCEAN);
+        let mid = GRAY.blend(
+            &Palette {
+                entries: [WHITE, BLACK],
+            },
+            128,
+        );
+        assert_eq!(mid.entries, [gray(128), gray(127)]);
+    }
+
+    #[test]
+    fn fade_towards() {
+        for step in [1, 7, 255] {
+            let mut p = FIRE;
+            let mut frames = 0;
+            while !p.fade_towards(&OCEAN, step) {
+                frames += 1;
+                assert!(frames <= 255 / step as usize + 1);
+                // never past the target
+                for ((c, a), b) in p.entries.iter().zip(FIRE.entries).zip(OCEAN.entries) {
+                    for (c, a, b) in [(c.r, a.r, b.r), (c.g, a.g, b.g), (c.b, a.b, b.b)] {
+                        assert!(c >= a.min(b) && c <= a.max(b));
+                    }
+                }
+            }
+            assert_eq!(p, OCEAN);
+            assert!(p.fade_towards(&OCEAN, step));
+        }
+    }
+}
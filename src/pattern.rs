@@ -0,0 +1,204 @@
+use crate::{hex::Neighbors, math::Vec2};
+
+// Seed patterns for the hex cellular automata, as text. Every line is a path from the
+// origin made of the steps e, w, ne, nw, se and sw (no separators, "." for the origin
+// itself), the cell the path ends on is toggled. Blank lines and lines starting with '#'
+// are skipped.
+
+pub struct Pattern {
+    pub name: &'static str,
+    pub text: &'static str,
+}
+
+pub const HEXLIFE: Pattern = Pattern {
+    name: "hexlife",
+    text: include_str!("patterns/hexlife.txt"),
+};
+
+pub const PATTERNS: &[Pattern] = &[
+    HEXLIFE,
+    Pattern {
+        name: "flower",
+        text: include_str!("patterns/flower.txt"),
+    },
+    Pattern {
+        name: "triangle",
+        text: include_str!("patterns/triangle.txt"),
+    },
+    Pattern {
+        name: "bar",
+        text: include_str!("patterns/bar.txt"),
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Pattern> {
+    PATTERNS.iter().find(|p| p.name == name)
+}
+
+// positions are 1-based
+#[derive(Debug, PartialEq, Eq)]
+pub enum Error {
+    UnknownStep { line: usize, column: usize },
+    // path ends after 'n' or 's'
+    Incomplete { line: usize, column: usize },
+}
+
+impl Pattern {
+    // cell of every line, in order
+    pub fn cells(&self) -> impl Iterator<Item = Result<Vec2, Error>> + 'static {
+        cells(self.text)
+    }
+
+    // call toggle for every cell, stops at the first error
+    pub fn load(&self, mut toggle: impl FnMut(Vec2)) -> Result<(), Error> {
+        for cell in self.cells() {
+            toggle(cell?);
+        }
+        Ok(())
+    }
+}
+
+pub fn cells(text: &str) -> impl Iterator<Item = Result<Vec2, Error>> + '_ {
+    text.lines()
+        .enumerate()
+        .filter(|(_, l)| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+        .map(|(i, l)| {
+            let indent = l.len() - l.trim_start().len();
+            parse_path(l.trim()).map_err(|e| match e {
+                Error::UnknownStep { column, .. } => Error::UnknownStep {
+                    line: i + 1,
+                    column: column + indent,
+                },
+                Error::Incomplete { column, .. } => Error::Incomplete {
+                    line: i + 1,
+                    column: column + indent,
+                },
+            })
+        })
+}
+
+// end point of a single path (errors report line 0)
+pub fn parse_path(path: &str) -> Result<Vec2, Error> {
+    if path == "." {
+        return Ok(Vec2::new(0, 0));
+    }
+    let unknown = |column| Error::UnknownStep { line: 0, column };
+    let mut v = Vec2::new(0, 0);
+    let mut steps = path.bytes().enumerate();
+    while let Some((i, c)) = steps.next() {
+        // direction as in CUBE_DIRECTIONS
+        let direction = match c {
+            b'e' => 0,
+            b'w' => 3,
+            b'n' | b's' => match (c, steps.next()) {
+                (b'n', Some((_, b'e'))) => 1,
+                (b'n', Some((_, b'w'))) => 2,
+                (b's', Some((_, b'w'))) => 4,
+                (b's', Some((_, b'e'))) => 5,
+                (_, Some((j, _))) => return Err(unknown(j + 1)),
+                (_, None) => {
+                    return Err(Error::Incomplete {
+                        line: 0,
+                        column: i + 2,
+                    })
+                }
+            },
+            _ => return Err(unknown(i + 1)),
+        };
+        v = v.neighbor(direction);
+    }
+    Ok(v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the parser formerly duplicated in hexlife.rs / hexlife2.rs
+    fn parse_old(line: &str) -> Vec2 {
+        let mut c = line.chars();
+        let mut x = 0i32;
+        let mut y = 0i32;
+        loop {
+            match c.next() {
+                Some('e') => x += 1,
+                Some('w') => x -= 1,
+                Some('s') => match c.next() {
+                    Some('e') => {
+                        x += y.abs() % 2;
+                        y += 1
+                    }
+                    Some('w') => {
+                        y += 1;
+                        x -= y.abs() % 2;
+                    }
+                    _ => break,
+                },
+                Some('n') => match c.next() {
+                    Some('e') => {
+                        x += y.abs() % 2;
+                        y -= 1
+                    }
+                    Some('w') => {
+                        y -= 1;
+                        x -= y.abs() % 2;
+                    }
+                    _ => break,
+                },
+                _ => break,
+            }
+        }
+        Vec2 { x, y }
+    }
+
+    #[test]
+    fn single_steps() {
+        let o = Vec2::new(0, 0);
+        for (d, step) in ["e", "ne", "nw", "w", "sw", "se"].iter().enumerate() {
+            assert_eq!(parse_path(step), Ok(o.neighbor(d)));
+        }
+        assert_eq!(parse_path("."), Ok(o));
+        assert_eq!(parse_path("ewnesw"), Ok(o));
+        assert_eq!(parse_path("nenw"), Ok(Vec2::new(0, -2)));
+    }
+
+    #[test]
+    fn library_matches_old_parser() {
+        for pattern in PATTERNS {
+            for cell in pattern.cells() {
+                assert!(cell.is_ok(), "{}: {:?}", pattern.name, cell);
+            }
+        }
+        let lines = HEXLIFE.text.lines().filter(|l| !l.starts_with('#'));
+        for (line, cell) in lines.zip(HEXLIFE.cells()) {
+            assert_eq!(cell, Ok(parse_old(line)), "{}", line);
+        }
+        assert_eq!(HEXLIFE.cells().count(), 34);
+        assert!(find("flower").is_some());
+        assert!(find("glider").is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let text = "# comment\n\neee\n  nex\nwwn\n";
+        let mut c = cells(text);
+        assert_eq!(c.next(), Some(Ok(Vec2::new(3, 0))));
+        assert_eq!(
+            c.next(),
+            Some(Err(Error::UnknownStep { line: 4, column: 5 }))
+        );
+        assert_eq!(
+            c.next(),
+            Some(Err(Error::Incomplete { line: 5, column: 4 }))
+        );
+        assert_eq!(c.next(), None);
+        let bad = Pattern {
+            name: "bad",
+            text: "e\nnx\n",
+        };
+        assert_eq!(
+            bad.load(|_| ()),
+            Err(Error::UnknownStep { line: 2, column: 2 })
+        );
+    }
+}
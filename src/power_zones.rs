@@ -1,17 +1,7 @@
-use crate::matrix::NUM_LEDS;
-
-const START0: usize = 0;
-const SIZE0: usize = 8 + 9 + 10 + 11 + 15 + 16 + 17;
-const START1: usize = SIZE0;
-const SIZE1: usize = 17 + 17 + 17 + 17;
-const START2: usize = START1 + SIZE1;
-const SIZE2: usize = 17 + 17 + 17 + 17;
-const START3: usize = START2 + SIZE2;
-const SIZE3: usize = 16 + 15 + 11 + 10 + 9 + 8;
-const END3: usize = START3 + SIZE3;
-pub const NUM_ZONES: usize = 4;
-const ZONES: [core::ops::Range<usize>; NUM_ZONES] =
-    [START0..START1, START1..START2, START2..START3, START3..END3];
+use crate::matrix::{Matrix, Shape};
+
+pub const NUM_ZONES: usize = Matrix::ZONES.len();
+const ZONES: &[core::ops::Range<usize>] = Matrix::ZONES;
 
 fn rgb8_to_power(c: &smart_leds::RGB8) -> u32 {
     let tmp = 122 * c.r as u32 + 121 * c.g as u32 + 121 * c.b as u32;
@@ -22,7 +12,7 @@ fn estimate_current(data: &[smart_leds::RGB8]) -> u32 {
     data.iter().map(rgb8_to_power).sum::<u32>()
 }
 
-pub fn estimate_current_all(data: &[smart_leds::RGB8; NUM_LEDS]) -> [u32; NUM_ZONES] {
+pub fn estimate_current_all(data: &[smart_leds::RGB8; Matrix::NUM_LEDS]) -> [u32; NUM_ZONES] {
     let mut out = [0; NUM_ZONES];
     for (i, range) in ZONES.iter().cloned().enumerate() {
         out[i] = 78 + estimate_current(&data[range]);
@@ -31,7 +21,7 @@ pub fn estimate_current_all(data: &[smart_leds::RGB8; NUM_LEDS]) -> [u32; NUM_ZO
 }
 
 pub fn limit_current(
-    data: &mut [smart_leds::RGB8; NUM_LEDS],
+    data: &mut [smart_leds::RGB8; Matrix::NUM_LEDS],
     limit: &[u32; NUM_ZONES],
 ) -> [Option<u32>; NUM_ZONES] {
     // const LIMIT: u32 = 1100;
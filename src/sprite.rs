@@ -0,0 +1,51 @@
+use smart_leds::RGB8;
+
+use crate::hex::Cube;
+use crate::math::Vec2;
+
+// Pixel storage of a sprite, row by row in odd-r layout (odd rows shifted right by half a cell).
+// Transparent pixels are None.
+pub enum Pixels<'a> {
+    Rgb(&'a [Option<RGB8>]),
+    Indexed {
+        palette: &'a [Option<RGB8>],
+        indices: &'a [u8],
+    },
+}
+
+pub struct Sprite<'a> {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Pixels<'a>,
+}
+
+impl Sprite<'_> {
+    pub fn get(&self, x: usize, y: usize) -> Option<RGB8> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let i = y * self.width + x;
+        match &self.pixels {
+            Pixels::Rgb(data) => data.get(i).copied().flatten(),
+            Pixels::Indexed { palette, indices } => indices
+                .get(i)
+                .and_then(|p| palette.get(*p as usize))
+                .copied()
+                .flatten(),
+        }
+    }
+
+    // cube coordinate of the sprite pixel (x, y) relative to the sprite center
+    pub fn local_cube(&self, x: usize, y: usize) -> Cube {
+        let center = Cube::from(Vec2::new(self.width as i32 / 2, self.height as i32 / 2));
+        Cube::from(Vec2::new(x as i32, y as i32)) - center
+    }
+
+    // all opaque pixels with their position relative to the sprite center
+    pub fn iter(&self) -> impl Iterator<Item = (Cube, RGB8)> + '_ {
+        (0..self.height).flat_map(move |y| {
+            (0..self.width)
+                .filter_map(move |x| self.get(x, y).map(|color| (self.local_cube(x, y), color)))
+        })
+    }
+}
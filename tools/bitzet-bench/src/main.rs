@@ -0,0 +1,115 @@
+use std::{hint::black_box, time::Instant};
+
+use bitset_core::BitSet;
+use mocca_matrix_embassy::{bitzet::Bitzet, hex::Cube, math::Vec2};
+
+type BitzetN = Bitzet<128>;
+
+// Compares the word skipping / region iterators of Bitzet against testing one bit at a
+// time (how ZOrderIterator used to work) and filtering a full iteration.
+
+// ZOrderIterator before skipping empty words
+fn iter_bitwise(s: &BitzetN) -> impl Iterator<Item = Vec2> + '_ {
+    (0..4).flat_map(move |q| {
+        (0..=s.max[q])
+            .filter(move |z| s.quadrants[q].bit_test(*z))
+            .map(move |z| {
+                let v = inverse(z as u32);
+                match q {
+                    0 => Vec2::new(v.x, v.y),
+                    1 => Vec2::new(-v.x, v.y),
+                    2 => Vec2::new(v.x, -v.y),
+                    _ => Vec2::new(-v.x, -v.y),
+                }
+            })
+    })
+}
+
+fn inverse(z: u32) -> Vec2 {
+    let compact = |mut v: u32| {
+        v &= 0x55555555;
+        v = (v | (v >> 1)) & 0x33333333;
+        v = (v | (v >> 2)) & 0x0F0F0F0F;
+        v = (v | (v >> 4)) & 0x00FF00FF;
+        (v | (v >> 8)) & 0x0000FFFF
+    };
+    Vec2::new(compact(z) as i32, compact(z >> 1) as i32)
+}
+
+// xorshift, good enough to scatter points
+struct Rng(u32);
+impl Rng {
+    fn range(&mut self, lo: i32, hi: i32) -> i32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        lo + (self.0 % (hi - lo + 1) as u32) as i32
+    }
+}
+
+// average time per call in microseconds, checking that both sides agree
+fn compare<A, B>(name: &str, rounds: u32, mut old: A, mut new: B)
+where
+    A: FnMut() -> Vec<Vec2>,
+    B: FnMut() -> Vec<Vec2>,
+{
+    assert_eq!(old(), new(), "{}: results differ", name);
+    let time = |f: &mut dyn FnMut() -> Vec<Vec2>| {
+        let start = Instant::now();
+        for _ in 0..rounds {
+            black_box(f());
+        }
+        start.elapsed().as_secs_f64() * 1e6 / rounds as f64
+    };
+    let (t_old, t_new) = (time(&mut old), time(&mut new));
+    println!(
+        "{:<28} {:>10.2}us {:>10.2}us {:>7.1}x",
+        name,
+        t_old,
+        t_new,
+        t_old / t_new
+    );
+}
+
+fn main() {
+    let cap = BitzetN::capacity();
+    let mut rng = Rng(0x2545f491);
+    println!("{:<28} {:>12} {:>12} {:>8}", "", "old", "new", "speedup");
+    for (density, count) in [("sparse", 50), ("medium", 1000), ("dense", 8000)] {
+        let s = (0..count)
+            .map(|_| Vec2::new(rng.range(-cap, cap), rng.range(-cap, cap)))
+            .collect::<BitzetN>();
+        let s = &s;
+
+        compare(
+            &format!("iter, {}", density),
+            2000,
+            || iter_bitwise(s).collect(),
+            || s.iter().collect(),
+        );
+
+        let (min, max) = (Vec2::new(-5, 3), Vec2::new(12, 20));
+        compare(
+            &format!("iter_in_rect, {}", density),
+            2000,
+            || {
+                s.iter()
+                    .filter(|v| (min.x..=max.x).contains(&v.x) && (min.y..=max.y).contains(&v.y))
+                    .collect()
+            },
+            || s.iter_in_rect(min, max).collect(),
+        );
+
+        let (center, radius) = (Cube::from_oddr(Vec2::new(10, -7)), 6);
+        compare(
+            &format!("iter_in_hex_range, {}", density),
+            2000,
+            || {
+                s.iter()
+                    .filter(|v| Cube::from_oddr(*v).distance(&center) <= radius)
+                    .collect()
+            },
+            || s.iter_in_hex_range(center, radius).collect(),
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::{env, fmt::Write, fs, io::BufReader, path::Path, process::exit};
+
+use image::{codecs::gif::GifDecoder, AnimationDecoder, RgbaImage};
+
+mod resample;
+
+use mocca_matrix_embassy::{
+    frames, hex,
+    math::Vec2,
+    matrix::{self, Matrix, Shape},
+};
+
+const USAGE: &str = "usage:
+    hexconv sprite <image> <width> [NAME]
+        resample image onto a <width> cells wide odd-r grid and print it as a
+        mocca_matrix_embassy::sprite::Sprite constant
+    hexconv frames <image.png|animation.gif> <output.hxf> [NAME]
+        resample still image or all gif frames onto the led matrix, write them in the
+        mocca_matrix_embassy::frames format and print an include_bytes! line for it";
+
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    let res = match args.get(1).map(|s| s.as_str()) {
+        Some("sprite") => sprite(&args[2..]),
+        Some("frames") => frames(&args[2..]),
+        _ => Err(USAGE.to_string()),
+    };
+    match res {
+        Ok(out) => print!("{}", out),
+        Err(err) => {
+            eprintln!("{}", err);
+            exit(1);
+        }
+    }
+}
+
+fn sprite(args: &[String]) -> Result<String, String> {
+    let (Some(path), Some(width)) = (args.first(), args.get(1)) else {
+        return Err(USAGE.to_string());
+    };
+    let width = width
+        .parse::<usize>()
+        .map_err(|e| format!("bad width {}: {}", width, e))?;
+    let name = args.get(2).cloned().unwrap_or_else(|| "SPRITE".to_string());
+    let img = image::open(path)
+        .map_err(|e| format!("failed to load {}: {}", path, e))?
+        .to_rgba8();
+
+    // image width covers `width` cells, top left corner on the edge of cell (0, 0)
+    let scale = img.width() as f32 / width as f32;
+    let height = ((img.height() as f32 / scale) / hex::SQRT3_2)
+        .round()
+        .max(1.0) as usize;
+    let cells = resample::accumulate(&img, scale, (-0.5, -0.5), width, height);
+
+    let mut out = String::new();
+    writeln!(out, "// generated by hexconv from {}", path).unwrap();
+    writeln!(out, "pub const {}: Sprite = Sprite {{", name).unwrap();
+    writeln!(out, "    width: {},", width).unwrap();
+    writeln!(out, "    height: {},", height).unwrap();
+    writeln!(out, "    pixels: Pixels::Rgb(&[").unwrap();
+    for row in cells.chunks(width) {
+        write!(out, "       ").unwrap();
+        for p in row.iter().map(resample::Cell::sprite_pixel) {
+            match p {
+                Some([r, g, b]) => write!(out, " Some(RGB8 {{ r: {}, g: {}, b: {} }}),", r, g, b),
+                None => write!(out, " None,"),
+            }
+            .unwrap();
+        }
+        writeln!(out).unwrap();
+    }
+    writeln!(out, "    ]),").unwrap();
+    writeln!(out, "}};").unwrap();
+    Ok(out)
+}
+
+fn frames(args: &[String]) -> Result<String, String> {
+    let (Some(path), Some(output)) = (args.first(), args.get(1)) else {
+        return Err(USAGE.to_string());
+    };
+    let name = args.get(2).cloned().unwrap_or_else(|| "FRAMES".to_string());
+    let images = load_frames(path)?;
+    if images.len() > u16::MAX as usize {
+        return Err(format!("too many frames: {}", images.len()));
+    }
+
+    let mut data = frames::header(images.len() as u16).to_vec();
+    for (img, delay_ms) in &images {
+        data.extend_from_slice(&delay_ms.to_le_bytes());
+        for rgb in resample_leds(img) {
+            data.extend_from_slice(&rgb);
+        }
+    }
+    fs::write(output, &data).map_err(|e| format!("failed to write {}: {}", output, e))?;
+
+    let file_name = Path::new(output)
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_else(|| output.clone());
+    Ok(format!(
+        "// generated by hexconv from {} ({} frames)\npub static {}: &[u8] = include_bytes!(\"{}\");\n",
+        path,
+        images.len(),
+        name,
+        file_name
+    ))
+}
+
+// all frames with their delay in ms (0 for still images)
+fn load_frames(path: &str) -> Result<Vec<(RgbaImage, u16)>, String> {
+    let load_err = |e: image::ImageError| format!("failed to load {}: {}", path, e);
+    let is_gif = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+    if !is_gif {
+        return Ok(vec![(image::open(path).map_err(load_err)?.to_rgba8(), 0)]);
+    }
+    let file = fs::File::open(path).map_err(|e| format!("failed to open {}: {}", path, e))?;
+    let decoder = GifDecoder::new(BufReader::new(file)).map_err(load_err)?;
+    decoder
+        .into_frames()
+        .map(|frame| {
+            let frame = frame.map_err(load_err)?;
+            let (n, d) = frame.delay().numer_denom_ms();
+            let delay_ms = (n / d.max(1)).clamp(1, u16::MAX as u32) as u16;
+            Ok((frame.into_buffer(), delay_ms))
+        })
+        .collect()
+}
+
+// fit the image into the bounding box of the matrix (keeping the aspect ratio) and
+// return the area weighted color of every led in led order (untransformed layout)
+fn resample_leds(img: &RgbaImage) -> Vec<[u8; 3]> {
+    let led_cells = matrix::MATRIX_Y
+        .flat_map(|y| matrix::MATRIX_X.map(move |x| (x, y)))
+        .filter_map(|(x, y)| Matrix::led_addr(x, y).ok().map(|led| (x, y, led)))
+        .collect::<Vec<_>>();
+
+    // bounding box of all led hexagons in hex pixel space
+    let (mut xmin, mut xmax, mut ymin, mut ymax) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+    for (x, y, _) in &led_cells {
+        let (px, py) = hex::Cube::from(Vec2::new(*x as i32, *y as i32)).to_pixel();
+        xmin = xmin.min(px - 0.5);
+        xmax = xmax.max(px + 0.5);
+        ymin = ymin.min(py - 0.5);
+        ymax = ymax.max(py + 0.5);
+    }
+    let scale = (img.width() as f32 / (xmax - xmin)).max(img.height() as f32 / (ymax - ymin));
+    let offset = (
+        (xmin + xmax - img.width() as f32 / scale) * 0.5,
+        (ymin + ymax - img.height() as f32 / scale) * 0.5,
+    );
+    let cells = resample::accumulate(img, scale, offset, Matrix::WIDTH, Matrix::HEIGHT);
+
+    let mut leds = vec![[0u8; 3]; Matrix::NUM_LEDS];
+    for (x, y, led) in led_cells {
+        leds[led] = cells[y * Matrix::WIDTH + x].on_black();
+    }
+    leds
+}
@@ -0,0 +1,74 @@
+use image::RgbaImage;
+
+use mocca_matrix_embassy::{hex, math::Vec2};
+
+// supersampling grid per source pixel
+const SUB: u32 = 4;
+
+#[derive(Clone, Default)]
+pub struct Cell {
+    // color weighted by alpha
+    rgb: [f32; 3],
+    alpha: f32,
+    count: f32,
+}
+
+impl Cell {
+    // averaged color, cells covered by less than 50% opaque area are transparent
+    pub fn sprite_pixel(&self) -> Option<[u8; 3]> {
+        if self.count == 0.0 || self.alpha / self.count < 0.5 {
+            None
+        } else {
+            Some(self.rgb.map(|c| to_u8(c / self.alpha)))
+        }
+    }
+    // averaged color composited onto black
+    pub fn on_black(&self) -> [u8; 3] {
+        if self.count == 0.0 {
+            [0; 3]
+        } else {
+            self.rgb.map(|c| to_u8(c / self.count))
+        }
+    }
+}
+
+fn to_u8(c: f32) -> u8 {
+    c.round().clamp(0.0, 255.0) as u8
+}
+
+// Area weighted resampling of a (square pixel) image onto a width x height odd-r grid. Image
+// pixel (u, v) is placed at (u / scale + offset.0, v / scale + offset.1) in hex pixel space
+// (see hex::Cube::to_pixel), each cell collects all sub samples falling into its hexagon.
+pub fn accumulate(
+    img: &RgbaImage,
+    scale: f32,
+    offset: (f32, f32),
+    width: usize,
+    height: usize,
+) -> Vec<Cell> {
+    let mut cells = vec![Cell::default(); width * height];
+    for (px, py, p) in img.enumerate_pixels() {
+        let alpha = p[3] as f32 / 255.0;
+        for sy in 0..SUB {
+            for sx in 0..SUB {
+                let u = px as f32 + (sx as f32 + 0.5) / SUB as f32;
+                let v = py as f32 + (sy as f32 + 0.5) / SUB as f32;
+                let cell = Vec2::from(hex::pixel_to_cube(
+                    u / scale + offset.0,
+                    v / scale + offset.1,
+                ));
+                if cell.x < 0 || cell.y < 0 || cell.x as usize >= width || cell.y as usize >= height
+                {
+                    continue;
+                }
+                let c = &mut cells[cell.y as usize * width + cell.x as usize];
+                for i in 0..3 {
+                    c.rgb[i] += p[i] as f32 * alpha;
+                }
+                c.alpha += alpha;
+                c.count += 1.0;
+            }
+        }
+    }
+    cells
+}